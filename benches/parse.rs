@@ -0,0 +1,63 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use m3u8_parser::{read_media_list, read_playlist};
+
+fn large_master_playlist(variants: usize) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+
+    for i in 0..variants {
+        playlist.push_str(&format!(
+            "#EXT-X-MEDIA:TYPE=VIDEO,GROUP-ID=\"group{0}\",NAME=\"{0}p\",AUTOSELECT=YES,DEFAULT=YES\n",
+            i
+        ));
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION=1920x1080,CODECS=\"avc1.64002A,mp4a.40.2\",VIDEO=\"group{}\",FRAME-RATE=60.000\n",
+            1_000_000 + i,
+            i
+        ));
+        playlist.push_str(&format!(
+            "https://video-weaver.lhr04.hls.ttvnw.net/v1/playlist/{}.m3u8\n",
+            i
+        ));
+    }
+
+    playlist
+}
+
+fn large_media_list(segments: usize) -> String {
+    let mut media_list = String::from(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:0\n",
+    );
+
+    for i in 0..segments {
+        media_list.push_str(&format!(
+            "#EXT-X-DATERANGE:ID=\"source-{0}\",CLASS=\"twitch-stream-source\",START-DATE=\"2020-11-18T14:12:40.956Z\",END-ON-NEXT=YES,X-TV-TWITCH-STREAM-SOURCE=\"live\"\n",
+            i
+        ));
+        media_list.push_str(&format!(
+            "#EXTINF:2.000,live\nhttps://video-edge.abs.hls.ttvnw.net/v1/segment/{}.ts\n",
+            i
+        ));
+    }
+
+    media_list
+}
+
+fn bench_read_playlist(c: &mut Criterion) {
+    let playlist = large_master_playlist(5_000);
+
+    c.bench_function("read_playlist 5000 variants", |b| {
+        b.iter(|| read_playlist(black_box(&playlist)).unwrap())
+    });
+}
+
+fn bench_read_media_list(c: &mut Criterion) {
+    let media_list = large_media_list(5_000);
+
+    c.bench_function("read_media_list 5000 dateranges", |b| {
+        b.iter(|| read_media_list(black_box(&media_list)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_read_playlist, bench_read_media_list);
+criterion_main!(benches);
@@ -9,6 +9,26 @@ pub enum M3U8ParserError<I> {
     IoError(String),
     ParseFloatError(String),
     ParseIntError(String),
+    InvalidSessionData(String),
+    InvalidSessionKey,
+    InvalidKeyMethod(String),
+    InvalidPlaylistType(String),
+    InvalidResolution(String),
+    AmbiguousPlaylistKind,
+    UndefinedVariable(String),
+    MissingSegmentUri,
+    /// A tag the playlist must have, named without its `#EXT-X-` prefix.
+    MissingRequiredTag(&'static str),
+    /// An attribute a tag must have, with the tag named without its `#EXT-X-` prefix.
+    MissingRequiredAttribute {
+        tag: &'static str,
+        attr: &'static str,
+    },
+    /// Wraps a failure with the 1-based line of the playlist it occurred on.
+    AtLine {
+        line: usize,
+        source: Box<M3U8ParserError<I>>,
+    },
 }
 
 impl<I: fmt::Display> fmt::Display for M3U8ParserError<I> {
@@ -20,6 +40,77 @@ impl<I: fmt::Display> fmt::Display for M3U8ParserError<I> {
             M3U8ParserError::IoError(e) => write!(f, "IO Error: {}", e),
             M3U8ParserError::ParseFloatError(e) => write!(f, "ParseFloat Error: {}", e),
             M3U8ParserError::ParseIntError(e) => write!(f, "ParseInt Error: {}", e),
+            M3U8ParserError::InvalidSessionData(data_id) => write!(
+                f,
+                "Invalid SESSION-DATA {}: exactly one of VALUE or URI must be present",
+                data_id
+            ),
+            M3U8ParserError::InvalidSessionKey => {
+                write!(f, "Invalid EXT-X-SESSION-KEY: METHOD must not be NONE")
+            }
+            M3U8ParserError::InvalidKeyMethod(method) => {
+                write!(f, "Invalid EXT-X-KEY METHOD {:?}", method)
+            }
+            M3U8ParserError::InvalidPlaylistType(playlist_type) => write!(
+                f,
+                "Invalid EXT-X-PLAYLIST-TYPE {:?}: expected VOD or EVENT",
+                playlist_type
+            ),
+            M3U8ParserError::InvalidResolution(resolution) => write!(
+                f,
+                "Invalid RESOLUTION {:?}: expected <width>x<height>",
+                resolution
+            ),
+            M3U8ParserError::AmbiguousPlaylistKind => write!(
+                f,
+                "Could not tell a master playlist from a media playlist: expected either \
+                 EXT-X-STREAM-INF or EXTINF/EXT-X-TARGETDURATION tags, but not both"
+            ),
+            M3U8ParserError::UndefinedVariable(name) => {
+                write!(f, "Undefined EXT-X-DEFINE variable {:?}", name)
+            }
+            M3U8ParserError::MissingSegmentUri => {
+                write!(f, "EXTINF is not followed by a segment URI")
+            }
+            M3U8ParserError::MissingRequiredTag(tag) => {
+                write!(f, "Missing required tag #EXT-X-{}", tag)
+            }
+            M3U8ParserError::MissingRequiredAttribute { tag, attr } => {
+                write!(f, "#EXT-X-{} is missing required attribute {}", tag, attr)
+            }
+            M3U8ParserError::AtLine { line, source } => {
+                write!(f, "error at line {}: {}", line, source)
+            }
+        }
+    }
+}
+
+impl M3U8ParserError<&str> {
+    /// Copies the offending input, so the error can outlive the data it was produced from.
+    pub fn into_owned(self) -> M3U8ParserError<String> {
+        match self {
+            M3U8ParserError::NomError(input, kind) => {
+                M3U8ParserError::NomError(input.to_owned(), kind)
+            }
+            M3U8ParserError::IoError(e) => M3U8ParserError::IoError(e),
+            M3U8ParserError::ParseFloatError(e) => M3U8ParserError::ParseFloatError(e),
+            M3U8ParserError::ParseIntError(e) => M3U8ParserError::ParseIntError(e),
+            M3U8ParserError::InvalidSessionData(e) => M3U8ParserError::InvalidSessionData(e),
+            M3U8ParserError::InvalidSessionKey => M3U8ParserError::InvalidSessionKey,
+            M3U8ParserError::InvalidKeyMethod(e) => M3U8ParserError::InvalidKeyMethod(e),
+            M3U8ParserError::InvalidPlaylistType(e) => M3U8ParserError::InvalidPlaylistType(e),
+            M3U8ParserError::InvalidResolution(e) => M3U8ParserError::InvalidResolution(e),
+            M3U8ParserError::AmbiguousPlaylistKind => M3U8ParserError::AmbiguousPlaylistKind,
+            M3U8ParserError::UndefinedVariable(e) => M3U8ParserError::UndefinedVariable(e),
+            M3U8ParserError::MissingSegmentUri => M3U8ParserError::MissingSegmentUri,
+            M3U8ParserError::MissingRequiredTag(tag) => M3U8ParserError::MissingRequiredTag(tag),
+            M3U8ParserError::MissingRequiredAttribute { tag, attr } => {
+                M3U8ParserError::MissingRequiredAttribute { tag, attr }
+            }
+            M3U8ParserError::AtLine { line, source } => M3U8ParserError::AtLine {
+                line,
+                source: Box::new(source.into_owned()),
+            },
         }
     }
 }
@@ -1,4 +1,11 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
 
 use indexmap::IndexMap;
 use nom::lib::std::fmt::Formatter;
@@ -7,29 +14,114 @@ use nom::Finish;
 use crate::error::M3U8ParserError;
 
 pub mod error;
+mod uri;
 
-#[derive(Debug)]
+pub type Attributes<'a> = IndexMap<Cow<'a, str>, Cow<'a, str>>;
+
+/// A [`Playlist`] that owns its data and can outlive the input it was parsed from.
+pub type OwnedPlaylist = Playlist<'static>;
+
+/// A [`MediaList`] that owns its data and can outlive the input it was parsed from.
+pub type OwnedMediaList = MediaList<'static>;
+
+fn into_owned_attributes(attributes: Attributes<'_>) -> Attributes<'static> {
+    attributes
+        .into_iter()
+        .map(|(k, v)| (Cow::Owned(k.into_owned()), Cow::Owned(v.into_owned())))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Playlist<'a> {
     pub ext_infos: Vec<PlaylistExtInfo<'a>>,
+    /// Whether `#EXT-X-INDEPENDENT-SEGMENTS` is present.
+    pub independent_segments: bool,
+    pub start: Option<Start>,
+    /// The variables given a `VALUE` by `#EXT-X-DEFINE`. Definitions using `IMPORT` or
+    /// `QUERYPARAM` get their value from outside the playlist, so they're kept in `ext_infos`.
+    pub defines: IndexMap<String, String>,
+    pub line_ending: LineEnding,
 }
 
 impl<'a> Playlist<'a> {
+    /// Copies any data still borrowed from the input, so the playlist can be kept after the
+    /// input is dropped.
+    pub fn into_owned(self) -> OwnedPlaylist {
+        Playlist {
+            ext_infos: self
+                .ext_infos
+                .into_iter()
+                .map(PlaylistExtInfo::into_owned)
+                .collect(),
+            independent_segments: self.independent_segments,
+            start: self.start,
+            defines: self.defines,
+            line_ending: self.line_ending,
+        }
+    }
+
     #[allow(unused)]
     pub fn playlist_names(&self) -> Vec<&str> {
         self.ext_infos
             .iter()
             .filter(|e| e.ext_type == PlaylistExtType::Media)
-            .map(|m| *m.attributes.get("NAME").unwrap_or(&"Unknown"))
+            .map(|m| m.attributes.get("NAME").map_or("Unknown", |n| n.as_ref()))
+            .collect()
+    }
+
+    /// Returns the `STREAM-INF` variants with their attributes parsed. Variants that fail to
+    /// parse, including ones missing the required `BANDWIDTH`, are left out, but remain in
+    /// `ext_infos`.
+    pub fn variants(&self) -> Vec<VariantStream> {
+        self.ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::StreamInf)
+            .filter_map(VariantStream::from_ext_info)
             .collect()
     }
 
+    /// Returns the URI of the first `STREAM-INF` variant in document order, whatever its
+    /// quality. See [`Playlist::best_variant_link`] to pick by bandwidth instead.
     pub fn first_playlist_link(&self) -> Option<&str> {
         self.ext_infos
+            .iter()
+            .find(|e| e.ext_type == PlaylistExtType::StreamInf)
+            .and_then(|p| p.attributes.get("URI").map(|u| u.as_ref()))
+    }
+
+    /// Returns the URI of the `STREAM-INF` variant with the highest `BANDWIDTH`, preferring
+    /// the earliest on ties. Variants without a valid `BANDWIDTH` are skipped.
+    pub fn best_variant_link(&self) -> Option<&str> {
+        self.ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::StreamInf)
+            .filter_map(|e| e.bandwidth().ok().flatten().map(|b| (b, e)))
+            // `max_by_key` keeps the last maximum, so search backwards to keep the first.
+            .rev()
+            .max_by_key(|(bandwidth, _)| *bandwidth)
+            .and_then(|(_, e)| e.attributes.get("URI").map(|u| u.as_ref()))
+    }
+
+    /// Returns the URI of the `STREAM-INF` variant with the tallest `RESOLUTION` not exceeding
+    /// `height`, or of the shortest variant if they all exceed it. Variants without a valid
+    /// `RESOLUTION` are skipped, and ties go to the earliest variant.
+    pub fn variant_for_height(&self, height: u32) -> Option<&str> {
+        let variants = self
+            .ext_infos
             .iter()
             .filter(|e| e.ext_type == PlaylistExtType::StreamInf)
-            .collect::<Vec<_>>()
-            .get(0)
-            .and_then(|p| p.attributes.get("URI").copied())
+            .filter_map(|e| e.resolution().ok().flatten().map(|r| (r.height, e)))
+            .collect::<Vec<_>>();
+
+        variants
+            .iter()
+            .filter(|(h, _)| *h <= height)
+            // `max_by_key` keeps the last maximum, so search backwards to keep the first.
+            .rev()
+            .max_by_key(|(h, _)| *h)
+            .or_else(|| variants.iter().min_by_key(|(h, _)| *h))
+            .and_then(|(_, e)| e.attributes.get("URI").map(|u| u.as_ref()))
     }
 
     #[allow(unused)]
@@ -57,527 +149,7905 @@ impl<'a> Playlist<'a> {
                     false
                 }
             })
-            .and_then(|e| e.attributes.get("URI").copied())
+            .and_then(|e| e.attributes.get("URI").map(|u| u.as_ref()))
     }
-}
 
-#[derive(Debug)]
-pub struct PlaylistExtInfo<'a> {
-    pub ext_type: PlaylistExtType,
-    pub attributes: IndexMap<&'a str, &'a str>,
-}
+    /// Replaces every `{$NAME}` reference in the attribute values, including variant URIs,
+    /// with the variable's value from `defines`.
+    ///
+    /// Returns [`M3U8ParserError::UndefinedVariable`] if a referenced variable isn't defined.
+    pub fn substitute_variables(&mut self) -> Result<(), M3U8ParserError<String>> {
+        for ext_info in &mut self.ext_infos {
+            substitute_attributes(&mut ext_info.attributes, &self.defines)?;
+        }
 
-#[derive(Debug, PartialEq)]
-pub enum PlaylistExtType {
-    Media,
-    StreamInf,
-    Unknown(String),
-}
+        Ok(())
+    }
 
-impl<T: AsRef<str>> From<T> for PlaylistExtType {
-    fn from(s: T) -> Self {
-        let s = s.as_ref().trim_start_matches("-X-").to_owned();
+    pub fn audio_renditions(&self) -> Vec<Rendition> {
+        self.renditions("AUDIO")
+    }
 
-        match s.as_str() {
-            "MEDIA" => Self::Media,
-            "STREAM-INF" => Self::StreamInf,
-            _ => Self::Unknown(s),
-        }
+    pub fn video_renditions(&self) -> Vec<Rendition> {
+        self.renditions("VIDEO")
     }
-}
 
-fn to_end(i: &str) -> nom::IResult<&str, &str> {
-    nom::branch::alt((not_newline, to_newline))(i)
-}
+    pub fn subtitle_renditions(&self) -> Vec<Rendition> {
+        self.renditions("SUBTITLES")
+    }
 
-fn to_newline(i: &str) -> nom::IResult<&str, &str> {
-    nom::bytes::complete::take_till(|c| c == '\n')(i)
-}
+    /// Returns the `CLOSED-CAPTIONS` renditions, which are carried in the video and so have no
+    /// `uri`.
+    pub fn closed_caption_renditions(&self) -> Vec<Rendition> {
+        self.renditions("CLOSED-CAPTIONS")
+    }
 
-fn not_newline(i: &str) -> nom::IResult<&str, &str> {
-    nom::bytes::complete::is_not("\n")(i)
-}
+    fn renditions(&self, media_type: &str) -> Vec<Rendition> {
+        self.ext_infos
+            .iter()
+            .filter(|e| {
+                e.ext_type == PlaylistExtType::Media
+                    && e.unquoted_attribute("TYPE") == Some(media_type)
+            })
+            .map(|e| Rendition {
+                group_id: e.unquoted_attribute("GROUP-ID").unwrap_or("").to_owned(),
+                name: e.unquoted_attribute("NAME").unwrap_or("").to_owned(),
+                language: e.unquoted_attribute("LANGUAGE").map(str::to_owned),
+                default: e.unquoted_attribute("DEFAULT") == Some("YES"),
+                autoselect: e.unquoted_attribute("AUTOSELECT") == Some("YES"),
+                uri: e.unquoted_attribute("URI").map(str::to_owned),
+                channel_spec: e.unquoted_attribute("CHANNELS").map(str::to_owned),
+            })
+            .collect()
+    }
 
-fn ext_identifier(i: &str) -> nom::IResult<&str, &str, M3U8ParserError<&str>> {
-    nom::bytes::complete::tag("#EXTM3U\n")(i)
-}
+    /// Returns the `SESSION-DATA` entries. Parsing already rejects entries that don't have
+    /// exactly one of `VALUE` and `URI`.
+    pub fn session_data(&self) -> Vec<SessionData> {
+        self.ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::SessionData)
+            .map(|e| SessionData {
+                data_id: e.session_data_attribute("DATA-ID").unwrap_or("").to_owned(),
+                value: e.attr("VALUE").map(Cow::into_owned),
+                uri: e.session_data_uri().map(str::to_owned),
+                language: e.session_data_language().map(str::to_owned),
+            })
+            .collect()
+    }
 
-fn ext_type<'a, T>(i: &'a str) -> nom::IResult<&str, T>
-where
-    T: From<&'a str>,
-{
-    let (i, ext_type_str) = nom::sequence::preceded(
-        nom::bytes::complete::tag("#EXT"),
-        nom::branch::alt((
-            nom::sequence::terminated(
-                nom::bytes::complete::is_not(":"),
-                nom::character::complete::char(':'),
-            ),
-            not_newline,
-        )),
-    )(i)?;
+    /// Returns the keys from the `SESSION-KEY` entries, which let a client load the keys for
+    /// its media playlists up front.
+    pub fn session_keys(&self) -> Vec<Key> {
+        self.ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::SessionKey)
+            .filter_map(|e| Key::from_attributes(&e.attributes).ok())
+            .collect()
+    }
 
-    Ok((i, T::from(ext_type_str)))
-}
+    /// Returns the `CONTENT-STEERING` server, or `None` if there isn't one.
+    pub fn content_steering(&self) -> Option<ContentSteering> {
+        let content_steering = self
+            .ext_infos
+            .iter()
+            .find(|e| e.ext_type == PlaylistExtType::ContentSteering)?;
 
-fn comma_sep_pair(i: &str) -> nom::IResult<&str, (&str, &str)> {
-    nom::sequence::separated_pair(
-        nom::bytes::complete::is_not(","),
-        nom::bytes::complete::tag(","),
-        nom::branch::alt((not_newline, to_newline)),
-    )(i)
-}
+        Some(ContentSteering {
+            server_uri: content_steering
+                .unquoted_attribute("SERVER-URI")?
+                .to_owned(),
+            pathway_id: content_steering
+                .unquoted_attribute("PATHWAY-ID")
+                .map(str::to_owned),
+        })
+    }
 
-fn read_quoted_attribute(i: &str) -> nom::IResult<&str, &str> {
-    nom::combinator::recognize(nom::sequence::delimited(
-        nom::character::complete::char('\"'),
-        nom::bytes::complete::is_not("\""),
-        nom::character::complete::char('\"'),
-    ))(i)
-}
+    /// Returns the `I-FRAME-STREAM-INF` trick-play variants.
+    pub fn iframe_variants(&self) -> Vec<&PlaylistExtInfo<'a>> {
+        self.ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::IFrameStreamInf)
+            .collect()
+    }
 
-fn attribute_key_val(i: &str) -> nom::IResult<&str, (&str, &str)> {
-    nom::sequence::separated_pair(
-        nom::bytes::complete::is_not("="),
-        nom::bytes::complete::tag("="),
-        nom::branch::alt((read_quoted_attribute, nom::bytes::complete::is_not(","))),
-    )(i)
-}
+    /// Picks the audio rendition in `group_id` that best matches `lang`: an exact `LANGUAGE`
+    /// match, then one sharing the primary language subtag (so `en-US` matches `en`), then
+    /// the group's `DEFAULT=YES` rendition, then the group's first rendition.
+    pub fn audio_rendition_for_language(
+        &self,
+        group_id: &str,
+        lang: &str,
+    ) -> Option<&PlaylistExtInfo<'a>> {
+        let primary_subtag = |l: &str| l.split('-').next().unwrap_or(l).to_ascii_lowercase();
 
-fn attributes(i: &str) -> nom::IResult<&str, IndexMap<&str, &str>> {
-    let (i, attributes_vec) =
-        nom::multi::separated_list0(nom::character::complete::char(','), attribute_key_val)(i)?;
+        let renditions = self
+            .ext_infos
+            .iter()
+            .filter(|e| {
+                e.ext_type == PlaylistExtType::Media
+                    && e.unquoted_attribute("TYPE") == Some("AUDIO")
+                    && e.unquoted_attribute("GROUP-ID") == Some(group_id)
+            })
+            .map(|e| (e, e.unquoted_attribute("LANGUAGE").unwrap_or("")))
+            .collect::<Vec<_>>();
 
-    let attributes_map = attributes_vec
-        .into_iter()
-        .fold(IndexMap::new(), |mut curr, next| {
-            curr.insert(next.0, next.1);
-            curr
-        });
+        renditions
+            .iter()
+            .find(|(_, language)| language.eq_ignore_ascii_case(lang))
+            .or_else(|| {
+                renditions
+                    .iter()
+                    .find(|(_, language)| primary_subtag(language) == primary_subtag(lang))
+            })
+            .or_else(|| {
+                renditions
+                    .iter()
+                    .find(|(e, _)| e.unquoted_attribute("DEFAULT") == Some("YES"))
+            })
+            .or_else(|| renditions.first())
+            .map(|(e, _)| *e)
+    }
 
-    Ok((i, attributes_map))
-}
+    /// Infers the codec of an audio `MEDIA` rendition that omits `CODECS` by looking up the
+    /// first `STREAM-INF` variant referencing its group and picking the audio codec out of that
+    /// variant's `CODECS` list.
+    pub fn infer_rendition_codec(&self, media: &PlaylistExtInfo) -> Option<&str> {
+        if media.unquoted_attribute("TYPE") != Some("AUDIO") {
+            return None;
+        }
+
+        let group_id = media.unquoted_attribute("GROUP-ID")?;
+
+        self.ext_infos
+            .iter()
+            .filter(|e| {
+                e.ext_type == PlaylistExtType::StreamInf
+                    && e.unquoted_attribute("AUDIO") == Some(group_id)
+            })
+            .filter_map(|e| e.attributes.get("CODECS"))
+            .find_map(|codecs| split_codecs(codecs).find(|c| is_audio_codec(c)))
+    }
 
-pub fn read_playlist(data: &str) -> Result<Playlist, M3U8ParserError<&str>> {
-    let (i, _) = ext_identifier(&data).finish()?;
+    /// Returns a copy of this playlist keeping only the `STREAM-INF` and `I-FRAME-STREAM-INF`
+    /// variants whose codecs all pass `supported`, along with the `MEDIA` renditions those
+    /// variants still reference. Variants that don't declare `CODECS` are kept.
+    pub fn filter_by_codec<F: Fn(&str) -> bool>(&self, supported: F) -> Playlist<'a> {
+        let is_variant = |e: &PlaylistExtInfo| {
+            matches!(
+                e.ext_type,
+                PlaylistExtType::StreamInf | PlaylistExtType::IFrameStreamInf
+            )
+        };
 
-    let mut remaining_lines = i.lines();
+        // `Option::is_none_or` would need Rust 1.82.
+        #[allow(clippy::unnecessary_map_or)]
+        let is_supported = |variant: &PlaylistExtInfo| {
+            variant
+                .attributes
+                .get("CODECS")
+                .map_or(true, |c| split_codecs(c).all(&supported))
+        };
 
-    let mut ext_infos = Vec::new();
+        let variants = self
+            .ext_infos
+            .iter()
+            .filter(|e| is_variant(e) && is_supported(e))
+            .collect::<Vec<_>>();
 
-    while let Some(line) = remaining_lines.next() {
-        let (i, ext_type) = ext_type::<PlaylistExtType>(line).finish()?;
-        let (_, mut attributes) = attributes(i).finish()?;
+        let is_referenced = |media: &PlaylistExtInfo| {
+            let rendition_type = media.attributes.get("TYPE");
+            let group_id = media.attributes.get("GROUP-ID").map(|g| unquote(g));
 
-        if ext_type == PlaylistExtType::StreamInf {
-            if let Some(stream_inf_location) = remaining_lines.next() {
-                attributes.insert("URI", stream_inf_location);
+            match (rendition_type, group_id) {
+                (Some(rendition_type), Some(group_id)) => variants.iter().any(|v| {
+                    v.attributes
+                        .get(rendition_type.as_ref())
+                        .is_some_and(|g| unquote(g) == group_id)
+                }),
+                _ => false,
             }
+        };
+
+        let ext_infos = self
+            .ext_infos
+            .iter()
+            .filter(|e| match e.ext_type {
+                PlaylistExtType::StreamInf | PlaylistExtType::IFrameStreamInf => is_supported(e),
+                PlaylistExtType::Media => is_referenced(e),
+                _ => true,
+            })
+            .cloned()
+            .collect();
+
+        Playlist {
+            ext_infos,
+            independent_segments: self.independent_segments,
+            start: self.start,
+            defines: self.defines.clone(),
+            line_ending: self.line_ending,
         }
+    }
 
-        ext_infos.push(PlaylistExtInfo {
-            ext_type,
-            attributes,
-        })
+    /// Writes the playlist to `output`. `#EXT-X-INDEPENDENT-SEGMENTS`, `#EXT-X-START` and the
+    /// `#EXT-X-DEFINE` variables are kept as fields rather than ext infos, so they're hoisted
+    /// into a block right after `#EXTM3U`, wherever they appeared in the input.
+    pub fn save<T: std::io::Write>(&self, output: &mut T) -> Result<(), M3U8ParserError<&str>> {
+        self.save_with(output, SaveOptions::default())
     }
 
-    Ok(Playlist { ext_infos })
-}
+    pub fn save_with<T: std::io::Write>(
+        &self,
+        output: &mut T,
+        options: SaveOptions,
+    ) -> Result<(), M3U8ParserError<&str>> {
+        let nl = options.line_ending.unwrap_or(self.line_ending).as_str();
 
-fn rejoin_attributes(attributes: &IndexMap<&str, &str>) -> String {
-    attributes
-        .iter()
-        .map(|(k, v)| {
-            if *k == "UNKNOWN" {
-                (*v).to_owned()
-            } else {
-                format!("{}={}", k, v)
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(",")
-}
+        write!(output, "{}", PlaylistDisplay { playlist: self, nl })?;
 
-#[derive(Debug)]
-pub struct MediaList<'a> {
-    pub version: u8,
-    pub target_duration: u8,
-    pub media_sequence: u32,
-    pub media_segments: Vec<MediaSegment>,
-    pub ext_infos: Vec<MediaExtInfo<'a>>,
-}
+        Ok(())
+    }
 
-impl<'a> MediaList<'a> {
-    pub fn save<T: std::io::Write>(&self, output: &mut T) -> Result<(), M3U8ParserError<&str>> {
+    fn write_to<W: fmt::Write>(&self, output: &mut W, nl: &str) -> fmt::Result {
         let ext_tag = "#EXT";
 
-        writeln!(output, "#EXTM3U")?;
-        writeln!(output, "{}-X-VERSION:{}", ext_tag, self.version)?;
-        writeln!(
-            output,
-            "{}-X-TARGETDURATION:{}",
-            ext_tag, self.target_duration
-        )?;
-        writeln!(
-            output,
-            "{}-X-MEDIA-SEQUENCE:{}",
-            ext_tag, self.media_sequence
-        )?;
+        write!(output, "#EXTM3U{}", nl)?;
 
-        for ext_info in &self.ext_infos {
-            match &ext_info.ext_type {
-                MediaExtType::Inf | MediaExtType::ProgramDateTime => (),
-                MediaExtType::Discontinuity => {
-                    writeln!(output, "{}-X-{}", ext_tag, ext_info.ext_type)?;
-                }
-                _ => {
-                    writeln!(
-                        output,
-                        "{}-X-{}:{}",
-                        ext_tag,
-                        ext_info.ext_type,
-                        rejoin_attributes(&ext_info.attributes)
-                    )?;
-                }
-            }
+        if self.independent_segments {
+            write!(
+                output,
+                "{}-X-{}{}",
+                ext_tag,
+                PlaylistExtType::IndependentSegments,
+                nl
+            )?;
         }
 
-        for segment in &self.media_segments {
-            if let Some(ref program_date_time) = segment.program_date_time {
-                writeln!(
-                    output,
-                    "{}-X-{}:{}",
-                    ext_tag,
-                    MediaExtType::ProgramDateTime,
-                    program_date_time
-                )?;
-            }
+        if let Some(start) = self.start {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                PlaylistExtType::Start,
+                start,
+                nl
+            )?;
+        }
 
-            writeln!(
+        for (name, value) in &self.defines {
+            write!(
                 output,
-                "{}{}:{:.3},{}\n{}",
+                "{}-X-{}:NAME=\"{}\",VALUE=\"{}\"{}",
                 ext_tag,
-                MediaExtType::Inf,
-                segment.duration,
-                segment.title.as_ref().unwrap_or(&"".to_owned()),
-                segment.uri,
+                PlaylistExtType::Define,
+                name,
+                value,
+                nl
+            )?;
+        }
+
+        for ext_info in &self.ext_infos {
+            write!(
+                output,
+                "{}{}{}",
+                ext_tag,
+                ext_info.ext_type.tag_prefix(),
+                ext_info.ext_type
             )?;
+
+            if ext_info.ext_type == PlaylistExtType::StreamInf {
+                // The URI was read from the line following the tag, so it's written back there.
+                let mut attributes = ext_info.attributes.clone();
+                let uri = attributes.shift_remove("URI");
+
+                write!(output, ":{}{}", rejoin_attributes(&attributes), nl)?;
+
+                if let Some(uri) = uri {
+                    write!(output, "{}{}", uri, nl)?;
+                }
+            } else if ext_info.attributes.is_empty() {
+                write!(output, "{}", nl)?;
+            } else {
+                write!(output, ":{}{}", rejoin_attributes(&ext_info.attributes), nl)?;
+            }
         }
 
         Ok(())
     }
 }
 
-#[derive(Debug)]
-pub struct MediaExtInfo<'a> {
-    pub ext_type: MediaExtType,
-    pub attributes: IndexMap<&'a str, &'a str>,
+/// Renders the playlist exactly as [`Playlist::save`] writes it.
+impl fmt::Display for Playlist<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write_to(f, self.line_ending.as_str())
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum MediaExtType {
-    Version,
-    TargetDuration,
-    MediaSequence,
-    DateRange,
-    Discontinuity,
-    Inf,
-    ProgramDateTime,
-    Unknown(String),
+/// Renders a playlist with an overridden line ending, as [`MediaListDisplay`] does for media
+/// lists.
+struct PlaylistDisplay<'a, 'b> {
+    playlist: &'b Playlist<'a>,
+    nl: &'b str,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct MediaSegment {
-    pub duration: f64,
-    pub title: Option<String>,
-    pub uri: String,
-    pub program_date_time: Option<String>,
+impl fmt::Display for PlaylistDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.playlist.write_to(f, self.nl)
+    }
 }
 
-impl fmt::Display for MediaExtType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            MediaExtType::Version => write!(f, "VERSION"),
-            MediaExtType::TargetDuration => write!(f, "TARGETDURATION"),
-            MediaExtType::MediaSequence => write!(f, "MEDIA-SEQUENCE"),
-            MediaExtType::DateRange => write!(f, "DATERANGE"),
-            MediaExtType::Discontinuity => write!(f, "DISCONTINUITY"),
-            MediaExtType::Inf => write!(f, "INF"),
-            MediaExtType::ProgramDateTime => write!(f, "PROGRAM-DATE-TIME"),
-            MediaExtType::Unknown(ext_type) => write!(f, "{}", ext_type),
-        }
+/// Parses a master playlist with [`read_playlist`], copying it so it doesn't borrow `s`.
+impl FromStr for OwnedPlaylist {
+    type Err = M3U8ParserError<String>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        read_playlist(s)
+            .map(Playlist::into_owned)
+            .map_err(M3U8ParserError::into_owned)
     }
 }
 
-impl<T: AsRef<str>> From<T> for MediaExtType {
-    fn from(s: T) -> Self {
-        let s = s.as_ref().trim_start_matches("-X-").to_owned();
+/// Parses a master playlist with [`read_playlist`], borrowing from `data`.
+impl<'a> TryFrom<&'a str> for Playlist<'a> {
+    type Error = M3U8ParserError<&'a str>;
 
-        match s.as_str() {
-            "VERSION" => Self::Version,
-            "TARGETDURATION" => Self::TargetDuration,
-            "MEDIA-SEQUENCE" => Self::MediaSequence,
-            "DATERANGE" => Self::DateRange,
-            "DISCONTINUITY" => Self::Discontinuity,
-            "INF" => Self::Inf,
-            "PROGRAM-DATE-TIME" => Self::ProgramDateTime,
-            _ => Self::Unknown(s),
-        }
+    fn try_from(data: &'a str) -> Result<Self, Self::Error> {
+        read_playlist(data)
     }
 }
 
-pub fn read_media_list(data: &str) -> Result<MediaList, M3U8ParserError<&str>> {
-    let (i, _) = ext_identifier(&data).finish()?;
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlaylistExtInfo<'a> {
+    pub ext_type: PlaylistExtType,
+    pub attributes: Attributes<'a>,
+}
+
+impl<'a> PlaylistExtInfo<'a> {
+    pub fn into_owned(self) -> PlaylistExtInfo<'static> {
+        PlaylistExtInfo {
+            ext_type: self.ext_type,
+            attributes: into_owned_attributes(self.attributes),
+        }
+    }
+
+    /// Returns the attribute with any balanced surrounding quotes removed and escaped quotes
+    /// unescaped, leaving enumerated and numeric values as they are. `attributes` keeps the
+    /// raw values for round-tripping.
+    pub fn attr(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.attributes.get(key).map(|v| attribute_value(v))
+    }
+
+    /// Sets the attribute to `value`, quoting it if the value it replaces was quoted. A new
+    /// attribute is written as given, so a quoted-string should include its quotes.
+    pub fn set_attr(&mut self, key: &str, value: impl Into<String>) {
+        set_attribute(&mut self.attributes, key, value.into());
+    }
+
+    /// Parses the `RESOLUTION` attribute, returning `Ok(None)` when it's absent.
+    pub fn resolution(&self) -> Result<Option<Resolution>, M3U8ParserError<&str>> {
+        self.unquoted_attribute("RESOLUTION")
+            .map(Resolution::parse)
+            .transpose()
+    }
+
+    /// Parses the `BANDWIDTH` attribute in bits per second, returning `Ok(None)` when it's
+    /// absent.
+    pub fn bandwidth(&self) -> Result<Option<u64>, M3U8ParserError<&str>> {
+        self.integer_attribute("BANDWIDTH")
+    }
+
+    /// Parses the `AVERAGE-BANDWIDTH` attribute in bits per second, returning `Ok(None)` when
+    /// it's absent.
+    pub fn average_bandwidth(&self) -> Result<Option<u64>, M3U8ParserError<&str>> {
+        self.integer_attribute("AVERAGE-BANDWIDTH")
+    }
+
+    /// Parses the `FRAME-RATE` attribute in frames per second, returning `Ok(None)` when it's
+    /// absent.
+    pub fn frame_rate(&self) -> Result<Option<f64>, M3U8ParserError<&str>> {
+        Ok(self
+            .unquoted_attribute("FRAME-RATE")
+            .map(|v| v.parse())
+            .transpose()?)
+    }
+
+    /// Returns the `HDCP-LEVEL` attribute, or `None` when it's absent.
+    pub fn hdcp_level(&self) -> Option<HdcpLevel> {
+        self.unquoted_attribute("HDCP-LEVEL").map(HdcpLevel::from)
+    }
+
+    /// Returns the `VIDEO-RANGE` attribute, or `None` when it's absent.
+    pub fn video_range(&self) -> Option<VideoRange> {
+        self.unquoted_attribute("VIDEO-RANGE").map(VideoRange::from)
+    }
+
+    fn integer_attribute(&self, key: &str) -> Result<Option<u64>, M3U8ParserError<&str>> {
+        Ok(self
+            .unquoted_attribute(key)
+            .map(|v| v.parse())
+            .transpose()?)
+    }
+
+    /// Splits the `CODECS` attribute into its codecs, returning an empty list when it's absent.
+    pub fn codecs(&self) -> Vec<&str> {
+        self.attributes
+            .get("CODECS")
+            .map_or_else(Vec::new, |c| split_codecs(c).collect())
+    }
+
+    /// Returns the content steering pathway a variant belongs to.
+    pub fn pathway_id(&self) -> Option<&str> {
+        self.unquoted_attribute("PATHWAY-ID")
+    }
+
+    pub fn session_data_value(&self) -> Option<&str> {
+        self.session_data_attribute("VALUE")
+    }
+
+    pub fn session_data_uri(&self) -> Option<&str> {
+        self.session_data_attribute("URI")
+    }
+
+    pub fn session_data_language(&self) -> Option<&str> {
+        self.session_data_attribute("LANGUAGE")
+    }
+
+    fn session_data_attribute(&self, key: &str) -> Option<&str> {
+        if self.ext_type != PlaylistExtType::SessionData {
+            return None;
+        }
+
+        self.unquoted_attribute(key)
+    }
+
+    fn unquoted_attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(|v| unquote(v))
+    }
+}
+
+/// A parsed `#EXT-X-SESSION-DATA`, carrying either an inline `value` or a `uri` to a JSON file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionData {
+    pub data_id: String,
+    pub value: Option<String>,
+    pub uri: Option<String>,
+    pub language: Option<String>,
+}
+
+/// A parsed `#EXT-X-MEDIA` alternative rendition.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rendition {
+    pub group_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub default: bool,
+    pub autoselect: bool,
+    /// The rendition's media playlist, or `None` when it's carried in the variant's own
+    /// segments, as closed captions always are.
+    pub uri: Option<String>,
+    /// The raw `CHANNELS` spec of an audio rendition, e.g. `6` or `16/JOC`.
+    pub channel_spec: Option<String>,
+}
+
+impl Rendition {
+    /// Returns the audio channel count, the first parameter of `CHANNELS`, or `None` when
+    /// it's absent or not a number.
+    pub fn channels(&self) -> Option<u16> {
+        self.channel_spec
+            .as_deref()?
+            .split('/')
+            .next()?
+            .parse()
+            .ok()
+    }
+}
+
+/// A parsed `#EXT-X-STREAM-INF` variant.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantStream {
+    pub uri: String,
+    /// The peak bit rate in bits per second.
+    pub bandwidth: u64,
+    pub average_bandwidth: Option<u64>,
+    pub resolution: Option<Resolution>,
+    pub codecs: Vec<String>,
+    pub frame_rate: Option<f64>,
+    /// The `GROUP-ID` of the audio renditions to play alongside this variant.
+    pub audio: Option<String>,
+    pub video: Option<String>,
+    pub subtitles: Option<String>,
+    /// The `GROUP-ID` of the closed captions, unless they're `NONE`.
+    pub closed_captions: Option<String>,
+}
+
+impl VariantStream {
+    fn from_ext_info(ext_info: &PlaylistExtInfo) -> Option<Self> {
+        let group_id = |key| ext_info.unquoted_attribute(key).map(str::to_owned);
+
+        Some(VariantStream {
+            uri: ext_info.attributes.get("URI")?.to_string(),
+            bandwidth: ext_info.bandwidth().ok()??,
+            average_bandwidth: ext_info.average_bandwidth().ok()?,
+            resolution: ext_info.resolution().ok()?,
+            codecs: ext_info.codecs().into_iter().map(str::to_owned).collect(),
+            frame_rate: ext_info.frame_rate().ok()?,
+            audio: group_id("AUDIO"),
+            video: group_id("VIDEO"),
+            subtitles: group_id("SUBTITLES"),
+            // `CLOSED-CAPTIONS=NONE` is an unquoted enumerated value, unlike a group id.
+            closed_captions: ext_info
+                .attributes
+                .get("CLOSED-CAPTIONS")
+                .filter(|c| *c != "NONE")
+                .map(|c| unquote(c).to_owned()),
+        })
+    }
+}
+
+/// A parsed `#EXT-X-CONTENT-STEERING`, pointing at the server that picks between pathways.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContentSteering {
+    pub server_uri: String,
+    /// The pathway to use until the steering manifest has been loaded.
+    pub pathway_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    pub fn parse(s: &str) -> Result<Self, M3U8ParserError<&str>> {
+        let invalid = || M3U8ParserError::InvalidResolution(s.to_owned());
+
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+
+        Ok(Resolution {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// The output protection a variant needs, as given by `HDCP-LEVEL`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HdcpLevel {
+    None,
+    Type0,
+    Type1,
+    /// Any value this crate doesn't know about yet.
+    Unknown(String),
+}
+
+impl From<&str> for HdcpLevel {
+    fn from(s: &str) -> Self {
+        match s {
+            "NONE" => HdcpLevel::None,
+            "TYPE-0" => HdcpLevel::Type0,
+            "TYPE-1" => HdcpLevel::Type1,
+            _ => HdcpLevel::Unknown(s.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for HdcpLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HdcpLevel::None => write!(f, "NONE"),
+            HdcpLevel::Type0 => write!(f, "TYPE-0"),
+            HdcpLevel::Type1 => write!(f, "TYPE-1"),
+            HdcpLevel::Unknown(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// The dynamic range of a variant's video, as given by `VIDEO-RANGE`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VideoRange {
+    Sdr,
+    Hlg,
+    Pq,
+    /// Any value this crate doesn't know about yet.
+    Unknown(String),
+}
+
+impl From<&str> for VideoRange {
+    fn from(s: &str) -> Self {
+        match s {
+            "SDR" => VideoRange::Sdr,
+            "HLG" => VideoRange::Hlg,
+            "PQ" => VideoRange::Pq,
+            _ => VideoRange::Unknown(s.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for VideoRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VideoRange::Sdr => write!(f, "SDR"),
+            VideoRange::Hlg => write!(f, "HLG"),
+            VideoRange::Pq => write!(f, "PQ"),
+            VideoRange::Unknown(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlaylistExtType {
+    Media,
+    StreamInf,
+    /// A trick-play variant, whose `URI` is an attribute on the same line.
+    IFrameStreamInf,
+    SessionData,
+    SessionKey,
+    IndependentSegments,
+    Start,
+    Define,
+    ContentSteering,
+    /// An unrecognised `#EXT-X-` tag, holding the name after `#EXT-X-`.
+    Unknown(String),
+    /// An unrecognised `#EXT` tag without the `-X-` prefix, holding the name after `#EXT`.
+    UnknownExt(String),
+}
+
+impl PlaylistExtType {
+    /// The part of the tag between `#EXT` and its `Display` name, so that
+    /// `format!("#EXT{}{}", t.tag_prefix(), t)` reproduces the original tag.
+    pub fn tag_prefix(&self) -> &'static str {
+        match self {
+            PlaylistExtType::UnknownExt(_) => "",
+            _ => "-X-",
+        }
+    }
+}
+
+impl fmt::Display for PlaylistExtType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaylistExtType::Media => write!(f, "MEDIA"),
+            PlaylistExtType::StreamInf => write!(f, "STREAM-INF"),
+            PlaylistExtType::IFrameStreamInf => write!(f, "I-FRAME-STREAM-INF"),
+            PlaylistExtType::SessionData => write!(f, "SESSION-DATA"),
+            PlaylistExtType::SessionKey => write!(f, "SESSION-KEY"),
+            PlaylistExtType::IndependentSegments => write!(f, "INDEPENDENT-SEGMENTS"),
+            PlaylistExtType::Start => write!(f, "START"),
+            PlaylistExtType::Define => write!(f, "DEFINE"),
+            PlaylistExtType::ContentSteering => write!(f, "CONTENT-STEERING"),
+            PlaylistExtType::Unknown(ext_type) | PlaylistExtType::UnknownExt(ext_type) => {
+                write!(f, "{}", ext_type)
+            }
+        }
+    }
+}
+
+impl<T: AsRef<str>> From<T> for PlaylistExtType {
+    fn from(s: T) -> Self {
+        let s = s.as_ref();
+
+        match s.strip_prefix("-X-") {
+            Some("MEDIA") => Self::Media,
+            Some("STREAM-INF") => Self::StreamInf,
+            Some("I-FRAME-STREAM-INF") => Self::IFrameStreamInf,
+            Some("SESSION-DATA") => Self::SessionData,
+            Some("SESSION-KEY") => Self::SessionKey,
+            Some("INDEPENDENT-SEGMENTS") => Self::IndependentSegments,
+            Some("START") => Self::Start,
+            Some("DEFINE") => Self::Define,
+            Some("CONTENT-STEERING") => Self::ContentSteering,
+            Some(name) => Self::Unknown(name.to_owned()),
+            None => Self::UnknownExt(s.to_owned()),
+        }
+    }
+}
+
+fn to_end(i: &str) -> nom::IResult<&str, &str> {
+    nom::branch::alt((not_newline, to_newline))(i)
+}
+
+fn to_newline(i: &str) -> nom::IResult<&str, &str> {
+    nom::bytes::complete::take_till(|c| c == '\n')(i)
+}
+
+fn not_newline(i: &str) -> nom::IResult<&str, &str> {
+    nom::bytes::complete::is_not("\n")(i)
+}
+
+fn ext_identifier(i: &str, mode: ParseMode) -> nom::IResult<&str, &str, M3U8ParserError<&str>> {
+    // IPTV playlists put attributes such as an EPG URL on the header line, which the
+    // specification doesn't allow.
+    let header_attributes = |i| match mode {
+        ParseMode::Lenient => nom::combinator::opt(nom::sequence::preceded(
+            nom::character::complete::char(' '),
+            nom::bytes::complete::is_not("\r\n"),
+        ))(i),
+        ParseMode::Strict => Ok((i, None)),
+    };
+
+    nom::combinator::recognize(nom::sequence::tuple((
+        nom::bytes::complete::tag("#EXTM3U"),
+        header_attributes,
+        nom::branch::alt((
+            nom::bytes::complete::tag("\r\n"),
+            nom::bytes::complete::tag("\n"),
+        )),
+    )))(i)
+}
+
+/// Some encoders prepend a UTF-8 byte order mark, which would otherwise hide `#EXTM3U`.
+fn strip_bom(data: &str) -> &str {
+    data.strip_prefix('\u{FEFF}').unwrap_or(data)
+}
+
+/// Parses the space separated `key=value` attributes some playlists put after `#EXTM3U`.
+fn header_attributes(i: &str) -> Attributes<'_> {
+    let (_, pairs) = nom::multi::many0(nom::sequence::preceded(
+        nom::character::complete::space0::<_, nom::error::Error<&str>>,
+        nom::sequence::separated_pair(
+            nom::bytes::complete::is_not("= "),
+            nom::bytes::complete::tag("="),
+            nom::branch::alt((read_quoted_attribute, nom::bytes::complete::is_not(" "))),
+        ),
+    ))(i)
+    .unwrap_or((i, Vec::new()));
+
+    pairs
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect()
+}
+
+/// Whether the line is blank or a comment, which starts with `#` but not `#EXT`. Both are
+/// ignored, including between an `#EXTINF` and its URI.
+fn is_comment(line: &str) -> bool {
+    let line = line.trim();
+
+    line.is_empty() || (line.starts_with('#') && !line.starts_with("#EXT"))
+}
+
+fn ext_type<'a, T>(i: &'a str) -> nom::IResult<&'a str, T>
+where
+    T: From<&'a str>,
+{
+    let (i, ext_type_str) = nom::sequence::preceded(
+        nom::bytes::complete::tag("#EXT"),
+        nom::branch::alt((
+            nom::sequence::terminated(
+                nom::bytes::complete::is_not(":"),
+                nom::character::complete::char(':'),
+            ),
+            not_newline,
+        )),
+    )(i)?;
+
+    Ok((i, T::from(ext_type_str)))
+}
+
+/// Splits an `#EXTINF` body into its duration and title. Everything after the first comma is
+/// the title verbatim, commas and all, and lenient parsing also accepts a duration without the
+/// comma, which gets an empty title.
+fn inf_duration_title(i: &str, mode: ParseMode) -> nom::IResult<&str, (&str, &str)> {
+    match mode {
+        ParseMode::Lenient => nom::branch::alt((
+            comma_sep_pair,
+            nom::combinator::map(not_newline, |duration| (duration, "")),
+        ))(i),
+        ParseMode::Strict => comma_sep_pair(i),
+    }
+}
+
+fn comma_sep_pair(i: &str) -> nom::IResult<&str, (&str, &str)> {
+    nom::sequence::separated_pair(
+        nom::bytes::complete::is_not(","),
+        nom::bytes::complete::tag(","),
+        nom::branch::alt((not_newline, to_newline)),
+    )(i)
+}
+
+/// Reads a double-quoted value, including its quotes. Quotes inside it may be escaped with a
+/// backslash, which [`unescape`] removes.
+fn read_quoted_attribute(i: &str) -> nom::IResult<&str, &str> {
+    nom::combinator::recognize(nom::sequence::delimited(
+        nom::character::complete::char('\"'),
+        nom::bytes::complete::escaped(
+            nom::bytes::complete::is_not("\"\\"),
+            '\\',
+            // Any character may be escaped, but `unescape` only drops the backslash before
+            // `\"` and `\\`.
+            nom::character::complete::anychar,
+        ),
+        nom::character::complete::char('\"'),
+    ))(i)
+}
+
+fn read_single_quoted_attribute(i: &str) -> nom::IResult<&str, &str> {
+    nom::combinator::recognize(nom::sequence::delimited(
+        nom::character::complete::char('\''),
+        nom::bytes::complete::is_not("'"),
+        nom::character::complete::char('\''),
+    ))(i)
+}
+
+/// Removes the backslashes escaping quotes and backslashes in a quoted value.
+fn unescape(s: &str) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && (next == '"' || next == '\\') => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+
+    Cow::Owned(unescaped)
+}
+
+/// Unquotes an attribute value, unescaping it only if it was a quoted-string.
+fn attribute_value(value: &str) -> Cow<'_, str> {
+    if value.starts_with('"') {
+        unescape(unquote(value))
+    } else {
+        Cow::Borrowed(unquote(value))
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    let quoted = |q| s.starts_with(q) && s.ends_with(q);
+
+    if s.len() >= 2 && (quoted('"') || quoted('\'')) {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+const AUDIO_CODEC_PREFIXES: [&str; 8] = [
+    "mp4a", "ac-3", "ec-3", "ac-4", "opus", "flac", "alac", "dts",
+];
+
+fn is_audio_codec(codec: &str) -> bool {
+    AUDIO_CODEC_PREFIXES
+        .iter()
+        .any(|prefix| codec.to_ascii_lowercase().starts_with(prefix))
+}
+
+/// Returns the name and value of an `#EXT-X-DEFINE` that gives its value inline.
+fn define(attributes: &Attributes) -> Option<(String, String)> {
+    let name = attributes.get("NAME")?;
+    let value = attributes.get("VALUE")?;
+
+    Some((unquote(name).to_owned(), unquote(value).to_owned()))
+}
+
+/// Replaces every `{$NAME}` reference in `s` with the variable's value from `defines`.
+fn substitute(
+    s: &str,
+    defines: &IndexMap<String, String>,
+) -> Result<String, M3U8ParserError<String>> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+
+    let mut substituted = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find("{$") {
+        let (before, reference) = rest.split_at(start);
+        substituted.push_str(before);
+
+        match reference[2..].split_once('}') {
+            Some((name, after)) if !name.is_empty() && name.chars().all(is_name_char) => {
+                let value = defines
+                    .get(name)
+                    .ok_or_else(|| M3U8ParserError::UndefinedVariable(name.to_owned()))?;
+
+                substituted.push_str(value);
+                rest = after;
+            }
+            // Not a variable reference, so it's kept as it is.
+            _ => {
+                substituted.push_str("{$");
+                rest = &reference[2..];
+            }
+        }
+    }
+
+    substituted.push_str(rest);
+
+    Ok(substituted)
+}
+
+fn substitute_attributes(
+    attributes: &mut Attributes,
+    defines: &IndexMap<String, String>,
+) -> Result<(), M3U8ParserError<String>> {
+    for value in attributes.values_mut() {
+        if value.contains("{$") {
+            *value = Cow::Owned(substitute(value, defines)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn split_codecs(codecs: &str) -> impl Iterator<Item = &str> {
+    unquote(codecs)
+        .split(',')
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+}
+
+/// Controls how closely the parsers follow the HLS specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParseMode {
+    /// Accepts common encoder quirks such as single-quoted attribute values.
+    Lenient,
+    /// Rejects anything the specification does not allow.
+    Strict,
+}
+
+fn attribute_key_val(i: &str, mode: ParseMode) -> nom::IResult<&str, (&str, &str)> {
+    let (i, (key, _)) = nom::sequence::pair(
+        nom::bytes::complete::is_not("="),
+        nom::bytes::complete::tag("="),
+    )(i)?;
+
+    match mode {
+        ParseMode::Lenient => {
+            // Some encoders pad the `=`, e.g. `BANDWIDTH = 1430857`. Only the padding is
+            // dropped, so whitespace inside quoted values is kept.
+            let (i, value) = nom::sequence::delimited(
+                nom::character::complete::space0,
+                nom::branch::alt((
+                    read_quoted_attribute,
+                    read_single_quoted_attribute,
+                    nom::bytes::complete::is_not(","),
+                )),
+                nom::character::complete::space0,
+            )(i)?;
+
+            Ok((i, (key.trim(), value.trim_end())))
+        }
+        ParseMode::Strict => {
+            let (i, value) = nom::branch::alt((
+                read_quoted_attribute,
+                nom::combinator::verify(nom::bytes::complete::is_not(","), |v: &str| {
+                    !v.starts_with(['"', '\''])
+                }),
+            ))(i)?;
+
+            Ok((i, (key, value)))
+        }
+    }
+}
+
+fn attributes(i: &str, mode: ParseMode) -> nom::IResult<&str, Attributes<'_>> {
+    let (rest, attributes_map) = attribute_list(i, mode)?;
+
+    if mode == ParseMode::Strict && !rest.is_empty() {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            rest,
+            nom::error::ErrorKind::Eof,
+        )));
+    }
+
+    Ok((rest, attributes_map))
+}
+
+/// Parses the body of an unrecognised tag as an attribute list, falling back to keeping the
+/// raw body under `UNKNOWN` when it doesn't rejoin to the same text, so `save` writes it back
+/// exactly.
+fn unknown_attributes<'a>(
+    line: &str,
+    i: &'a str,
+    mode: ParseMode,
+) -> nom::IResult<&'a str, Attributes<'a>> {
+    match attribute_list(i, mode) {
+        Ok((rest, attributes))
+            if rest.is_empty() && !attributes.is_empty() && rejoin_attributes(&attributes) == i =>
+        {
+            Ok((rest, attributes))
+        }
+        _ => {
+            let (rest, unknown_str) = to_end(i)?;
+
+            let mut attributes = IndexMap::new();
+
+            // Tags without a colon, like `#EXTFOO`, get no value so `save` omits the colon.
+            if line.contains(':') {
+                attributes.insert("UNKNOWN".into(), unknown_str.into());
+            }
+
+            Ok((rest, attributes))
+        }
+    }
+}
+
+fn attribute_list(i: &str, mode: ParseMode) -> nom::IResult<&str, Attributes<'_>> {
+    // Every attribute contains an '=', so this is an upper bound on the number of entries.
+    let mut attributes_map = IndexMap::with_capacity(i.bytes().filter(|b| *b == b'=').count());
+
+    let (mut i, (key, value)) = match attribute_key_val(i, mode) {
+        Ok(res) => res,
+        Err(nom::Err::Error(_)) => return Ok((i, attributes_map)),
+        Err(e) => return Err(e),
+    };
+
+    attributes_map.insert(key.into(), value.into());
+
+    loop {
+        let after_sep = match i.strip_prefix(',') {
+            Some(after_sep) => after_sep,
+            None => return Ok((i, attributes_map)),
+        };
+
+        match attribute_key_val(after_sep, mode) {
+            Ok((rest, (key, value))) => {
+                attributes_map.insert(key.into(), value.into());
+                i = rest;
+            }
+            Err(nom::Err::Error(_)) => return Ok((i, attributes_map)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A playlist whose kind was detected from its tags by [`parse`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::large_enum_variant)]
+pub enum PlaylistKind<'a> {
+    Master(Playlist<'a>),
+    Media(MediaList<'a>),
+}
+
+/// Parses `data` as a master playlist if it has `#EXT-X-STREAM-INF` or
+/// `#EXT-X-I-FRAME-STREAM-INF` tags, or as a media
+/// playlist if it has `#EXTINF` or `#EXT-X-TARGETDURATION` tags.
+///
+/// Returns [`M3U8ParserError::AmbiguousPlaylistKind`] if it has both or neither.
+pub fn parse(data: &str) -> Result<PlaylistKind<'_>, M3U8ParserError<&str>> {
+    let mut is_master = false;
+    let mut is_media = false;
+
+    for line in data.lines() {
+        if line.starts_with("#EXT-X-STREAM-INF") || line.starts_with("#EXT-X-I-FRAME-STREAM-INF") {
+            is_master = true;
+        } else if line.starts_with("#EXTINF") || line.starts_with("#EXT-X-TARGETDURATION") {
+            is_media = true;
+        }
+    }
+
+    match (is_master, is_media) {
+        (true, false) => read_playlist(data).map(PlaylistKind::Master),
+        (false, true) => read_media_list(data).map(PlaylistKind::Media),
+        _ => Err(M3U8ParserError::AmbiguousPlaylistKind),
+    }
+}
+
+pub fn read_playlist(data: &str) -> Result<Playlist<'_>, M3U8ParserError<&str>> {
+    read_playlist_mode(data, ParseMode::Lenient)
+}
+
+/// Like [`read_playlist`], but rejects input that does not follow the specification, such as
+/// single-quoted attribute values.
+pub fn read_playlist_strict(data: &str) -> Result<Playlist<'_>, M3U8ParserError<&str>> {
+    read_playlist_mode(data, ParseMode::Strict)
+}
+
+/// Reads the whole of `reader` and parses it as a master playlist.
+pub fn read_playlist_from<R: Read>(
+    reader: &mut R,
+) -> Result<OwnedPlaylist, M3U8ParserError<String>> {
+    let mut data = String::new();
+    reader.read_to_string(&mut data)?;
+
+    read_playlist(&data)
+        .map(Playlist::into_owned)
+        .map_err(M3U8ParserError::into_owned)
+}
+
+fn read_playlist_mode(data: &str, mode: ParseMode) -> Result<Playlist<'_>, M3U8ParserError<&str>> {
+    let mut line = 1;
+
+    read_playlist_lines(data, mode, &mut line).map_err(|source| M3U8ParserError::AtLine {
+        line,
+        source: Box::new(source),
+    })
+}
+
+/// Parses the playlist, keeping `line_number` at the line currently being read so that
+/// failures can be reported against it.
+fn read_playlist_lines<'a>(
+    data: &'a str,
+    mode: ParseMode,
+    line_number: &mut usize,
+) -> Result<Playlist<'a>, M3U8ParserError<&'a str>> {
+    let (i, identifier) = ext_identifier(strip_bom(data), mode).finish()?;
+
+    let line_ending = if identifier.ends_with("\r\n") {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    };
+
+    // The header is line 1, and a tag and the URI following it are reported by the tag's line.
+    let mut remaining_lines = i.lines().zip(2..).filter(|(line, _)| !is_comment(line));
+
+    let mut ext_infos = Vec::new();
+    let mut independent_segments = false;
+    let mut start = None;
+    let mut defines = IndexMap::new();
+
+    while let Some((line, number)) = remaining_lines.next() {
+        *line_number = number;
+
+        let (i, ext_type) = ext_type::<PlaylistExtType>(line).finish()?;
+
+        if ext_type == PlaylistExtType::IndependentSegments {
+            independent_segments = true;
+            continue;
+        }
+
+        let (_, mut attributes) = match ext_type {
+            PlaylistExtType::Unknown(_) | PlaylistExtType::UnknownExt(_) => {
+                unknown_attributes(line, i, mode)
+            }
+            _ => attributes(i, mode),
+        }
+        .finish()?;
+
+        if ext_type == PlaylistExtType::Start {
+            start = Some(Start::from_attributes(&attributes)?);
+            continue;
+        }
+
+        if ext_type == PlaylistExtType::Define {
+            if let Some((name, value)) = define(&attributes) {
+                defines.insert(name, value);
+                continue;
+            }
+        }
+
+        if mode == ParseMode::Strict {
+            let (tag, required): (_, &[_]) = match ext_type {
+                PlaylistExtType::StreamInf => ("STREAM-INF", &["BANDWIDTH"]),
+                PlaylistExtType::IFrameStreamInf => ("I-FRAME-STREAM-INF", &["BANDWIDTH", "URI"]),
+                _ => ("", &[]),
+            };
+
+            if let Some(&attr) = required.iter().find(|&&a| !attributes.contains_key(a)) {
+                return Err(M3U8ParserError::MissingRequiredAttribute { tag, attr });
+            }
+        }
+
+        if ext_type == PlaylistExtType::StreamInf {
+            if let Some((stream_inf_location, _)) = remaining_lines.next() {
+                attributes.insert("URI".into(), stream_inf_location.into());
+            }
+        }
+
+        if ext_type == PlaylistExtType::SessionData
+            && attributes.contains_key("VALUE") == attributes.contains_key("URI")
+        {
+            let data_id = attributes.get("DATA-ID").map_or("", |d| unquote(d));
+
+            return Err(M3U8ParserError::InvalidSessionData(data_id.to_owned()));
+        }
+
+        if ext_type == PlaylistExtType::SessionKey
+            && Key::from_attributes(&attributes)?.method == KeyMethod::None
+        {
+            return Err(M3U8ParserError::InvalidSessionKey);
+        }
+
+        ext_infos.push(PlaylistExtInfo {
+            ext_type,
+            attributes,
+        })
+    }
+
+    Ok(Playlist {
+        ext_infos,
+        independent_segments,
+        start,
+        defines,
+        line_ending,
+    })
+}
+
+/// Sets `key` to `value`, quoting it if the value it replaces was quoted. The quoted value is
+/// written with double quotes, escaping any quotes and backslashes in it.
+fn set_attribute(attributes: &mut Attributes, key: &str, value: String) {
+    let quoted = attributes
+        .get(key)
+        .is_some_and(|v| v.starts_with('"') || v.starts_with('\''));
+
+    let value = if quoted {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value
+    };
+
+    attributes.insert(Cow::Owned(key.to_owned()), Cow::Owned(value));
+}
+
+fn rejoin_attributes(attributes: &Attributes) -> String {
+    attributes
+        .iter()
+        .map(|(k, v)| {
+            if k == "UNKNOWN" {
+                v.to_string()
+            } else {
+                format!("{}={}", k, v)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaList<'a> {
+    pub version: u8,
+    pub playlist_type: Option<PlaylistType>,
+    pub target_duration: u8,
+    pub media_sequence: u32,
+    pub discontinuity_sequence: u32,
+    pub media_segments: Vec<MediaSegment>,
+    pub ext_infos: Vec<MediaExtInfo<'a>>,
+    pub end_list: bool,
+    /// Whether `#EXT-X-INDEPENDENT-SEGMENTS` is present.
+    pub independent_segments: bool,
+    /// Whether `#EXT-X-I-FRAMES-ONLY` is present, i.e. each segment is a single I-frame,
+    /// usually given as a `byte_range` of a shared file.
+    pub iframes_only: bool,
+    pub start: Option<Start>,
+    pub server_control: Option<ServerControl>,
+    /// The variables given a `VALUE` by `#EXT-X-DEFINE`. Definitions using `IMPORT` or
+    /// `QUERYPARAM` get their value from outside the playlist, so they're kept in `ext_infos`.
+    pub defines: IndexMap<String, String>,
+    /// The `#EXT-X-PRELOAD-HINT` resources a player can request ahead of the next update.
+    pub preload_hints: Vec<PreloadHint>,
+    /// The `#EXT-X-RENDITION-REPORT`s describing the other renditions' latest segments.
+    pub rendition_reports: Vec<RenditionReport>,
+    /// Set when this is a delta update that leaves out segments with `#EXT-X-SKIP`.
+    pub skip: Option<Skip>,
+    pub line_ending: LineEnding,
+    /// Non-standard attributes following `#EXTM3U` on the header line, as used by IPTV
+    /// playlists, e.g. `url-tvg`.
+    pub header_attributes: Attributes<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlaylistType {
+    Vod,
+    Event,
+}
+
+impl fmt::Display for PlaylistType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaylistType::Vod => write!(f, "VOD"),
+            PlaylistType::Event => write!(f, "EVENT"),
+        }
+    }
+}
+
+/// The preferred point to start playback from, as given by `#EXT-X-START`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Start {
+    /// Seconds from the start of the playlist, or from its end if negative.
+    pub time_offset: f64,
+    pub precise: bool,
+}
+
+impl Start {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        let time_offset = attributes
+            .get("TIME-OFFSET")
+            .map_or("", |t| t.as_ref())
+            .parse::<f64>()?;
+
+        Ok(Start {
+            time_offset,
+            precise: attributes.get("PRECISE").is_some_and(|p| p == "YES"),
+        })
+    }
+}
+
+impl fmt::Display for Start {
+    /// Writes the start point as an attribute list.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "TIME-OFFSET={}", self.time_offset)?;
+
+        if self.precise {
+            write!(f, ",PRECISE=YES")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SaveOptions {
+    /// Overrides the line ending detected while parsing.
+    pub line_ending: Option<LineEnding>,
+}
+
+/// Selects the fixes [`MediaList::normalize`] applies.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NormalizeOptions {
+    pub recompute_target_duration: bool,
+    /// Sorts each ext info's attributes by key, for stable diffs between playlists.
+    pub sort_attributes: bool,
+    /// Removes the `DISCONTINUITY` ext infos kept alongside `MediaSegment::discontinuity`
+    /// for backward compatibility. `save` only writes the flag on the segment.
+    pub remove_detached_discontinuities: bool,
+    /// Moves the `DISCONTINUITY` ext infos so there's exactly one before every segment with
+    /// `discontinuity` set, e.g. after segments were removed or flagged by hand. Runs before
+    /// `remove_detached_discontinuities`.
+    pub realign_discontinuities: bool,
+    /// Fills in the offset of each `#EXT-X-BYTERANGE` that omits it, from the end of the
+    /// previous segment's range of the same resource.
+    pub resolve_byte_range_offsets: bool,
+    /// Makes each segment share the key and map of the segment before it when they only differ
+    /// in spelled-out defaults, such as `KEYFORMAT="identity"`, so `save` writes one tag for
+    /// the run instead of one per change.
+    pub collapse_keys_and_maps: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NormalizeChange {
+    TargetDurationChanged {
+        previous: u8,
+        current: u8,
+    },
+    AttributesSorted {
+        index: usize,
+    },
+    DetachedDiscontinuitiesRemoved {
+        count: usize,
+    },
+    /// `count` discontinuity ext infos were added or removed to match the segments.
+    DiscontinuitiesRealigned {
+        count: usize,
+    },
+    /// The segment at `index` had its byte range offset filled in.
+    ByteRangeOffsetResolved {
+        index: usize,
+        offset: u64,
+    },
+    /// `count` redundant `KEY` or `MAP` tags were collapsed into the ones before them.
+    KeysAndMapsCollapsed {
+        count: usize,
+    },
+}
+
+impl<'a> MediaList<'a> {
+    /// Copies any data still borrowed from the input, so the media list can be kept after the
+    /// input is dropped.
+    pub fn into_owned(self) -> OwnedMediaList {
+        MediaList {
+            version: self.version,
+            playlist_type: self.playlist_type,
+            target_duration: self.target_duration,
+            media_sequence: self.media_sequence,
+            discontinuity_sequence: self.discontinuity_sequence,
+            media_segments: self.media_segments,
+            ext_infos: self
+                .ext_infos
+                .into_iter()
+                .map(MediaExtInfo::into_owned)
+                .collect(),
+            end_list: self.end_list,
+            independent_segments: self.independent_segments,
+            iframes_only: self.iframes_only,
+            start: self.start,
+            server_control: self.server_control,
+            defines: self.defines,
+            preload_hints: self.preload_hints,
+            rendition_reports: self.rendition_reports,
+            skip: self.skip,
+            line_ending: self.line_ending,
+            header_attributes: into_owned_attributes(self.header_attributes),
+        }
+    }
+
+    /// Writes the media list to `output`. Playlist-wide tags kept as fields, such as
+    /// `#EXT-X-START`, `#EXT-X-SERVER-CONTROL` and the `#EXT-X-DEFINE` variables, are hoisted
+    /// into a block right after `#EXTM3U`, wherever they appeared in the input. Other tags are
+    /// written before the segment at their `position`.
+    pub fn save<T: std::io::Write>(&self, output: &mut T) -> Result<(), M3U8ParserError<&str>> {
+        self.save_with(output, SaveOptions::default())
+    }
+
+    pub fn save_with<T: std::io::Write>(
+        &self,
+        output: &mut T,
+        options: SaveOptions,
+    ) -> Result<(), M3U8ParserError<&str>> {
+        let nl = options.line_ending.unwrap_or(self.line_ending).as_str();
+
+        write!(
+            output,
+            "{}",
+            MediaListDisplay {
+                media_list: self,
+                nl
+            }
+        )?;
+
+        Ok(())
+    }
+
+    fn write_to<W: fmt::Write>(&self, output: &mut W, nl: &str) -> fmt::Result {
+        let ext_tag = "#EXT";
+
+        write!(output, "#EXTM3U")?;
+
+        for (key, value) in &self.header_attributes {
+            write!(output, " {}={}", key, value)?;
+        }
+
+        write!(output, "{}", nl)?;
+        write!(output, "{}-X-VERSION:{}{}", ext_tag, self.version, nl)?;
+
+        if self.iframes_only {
+            write!(output, "{}-X-{}{}", ext_tag, MediaExtType::IFramesOnly, nl)?;
+        }
+
+        if let Some(playlist_type) = self.playlist_type {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::PlaylistType,
+                playlist_type,
+                nl
+            )?;
+        }
+
+        write!(
+            output,
+            "{}-X-TARGETDURATION:{}{}",
+            ext_tag, self.target_duration, nl
+        )?;
+        write!(
+            output,
+            "{}-X-MEDIA-SEQUENCE:{}{}",
+            ext_tag, self.media_sequence, nl
+        )?;
+
+        if self.discontinuity_sequence != 0 {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::DiscontinuitySequence,
+                self.discontinuity_sequence,
+                nl
+            )?;
+        }
+
+        if self.independent_segments {
+            write!(
+                output,
+                "{}-X-{}{}",
+                ext_tag,
+                MediaExtType::IndependentSegments,
+                nl
+            )?;
+        }
+
+        if let Some(start) = self.start {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::Start,
+                start,
+                nl
+            )?;
+        }
+
+        if let Some(server_control) = self.server_control {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::ServerControl,
+                server_control,
+                nl
+            )?;
+        }
+
+        for (name, value) in &self.defines {
+            write!(
+                output,
+                "{}-X-{}:NAME=\"{}\",VALUE=\"{}\"{}",
+                ext_tag,
+                MediaExtType::Define,
+                name,
+                value,
+                nl
+            )?;
+        }
+
+        // The sort is stable, so tags between the same segments keep their order.
+        let segment_count = self.media_segments.len();
+        let mut ext_infos = self.ext_infos.iter().collect::<Vec<_>>();
+        ext_infos.sort_by_key(|e| e.position.min(segment_count));
+
+        let mut ext_infos = ext_infos.into_iter().peekable();
+
+        while let Some(ext_info) = ext_infos.next_if(|e| e.position == 0) {
+            ext_info.write_to(output, nl)?;
+        }
+
+        // The skipped segments are the ones that would have come before the first segment.
+        if let Some(ref skip) = self.skip {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::Skip,
+                skip,
+                nl
+            )?;
+        }
+
+        let mut active_key = None;
+        let mut active_map = None;
+        let mut active_bitrate = None;
+
+        for (index, segment) in self.media_segments.iter().enumerate() {
+            while let Some(ext_info) = ext_infos.next_if(|e| e.position <= index) {
+                ext_info.write_to(output, nl)?;
+            }
+
+            if segment.cue_in {
+                write!(output, "{}-X-{}{}", ext_tag, MediaExtType::CueIn, nl)?;
+            }
+
+            if let Some(ref cue_out) = segment.cue_out {
+                write!(output, "{}-X-{}", ext_tag, MediaExtType::CueOut)?;
+
+                if let Some(duration) = cue_out.duration {
+                    write!(output, ":{}", duration)?;
+                }
+
+                write!(output, "{}", nl)?;
+            }
+
+            if segment.discontinuity {
+                write!(
+                    output,
+                    "{}-X-{}{}",
+                    ext_tag,
+                    MediaExtType::Discontinuity,
+                    nl
+                )?;
+            }
+
+            // There's no way to end a map, so it's only written when a new one takes effect.
+            if segment.map.is_some() && segment.map.as_ref() != active_map {
+                if let Some(ref map) = segment.map {
+                    write!(output, "{}-X-{}:{}{}", ext_tag, MediaExtType::Map, map, nl)?;
+                }
+
+                active_map = segment.map.as_ref();
+            }
+
+            if segment.key.as_ref() != active_key {
+                match segment.key {
+                    Some(ref key) => {
+                        write!(output, "{}-X-{}:{}{}", ext_tag, MediaExtType::Key, key, nl)?
+                    }
+                    None => write!(
+                        output,
+                        "{}-X-{}:METHOD={}{}",
+                        ext_tag,
+                        MediaExtType::Key,
+                        KeyMethod::None,
+                        nl
+                    )?,
+                }
+
+                active_key = segment.key.as_ref();
+            }
+
+            // Like a map, a bitrate can't be ended, so it's only written when it changes.
+            if segment.bitrate.is_some() && segment.bitrate != active_bitrate {
+                if let Some(bitrate) = segment.bitrate {
+                    write!(
+                        output,
+                        "{}-X-{}:{}{}",
+                        ext_tag,
+                        MediaExtType::Bitrate,
+                        bitrate,
+                        nl
+                    )?;
+                }
+
+                active_bitrate = segment.bitrate;
+            }
+
+            if let Some(ref program_date_time) = segment.program_date_time {
+                write!(
+                    output,
+                    "{}-X-{}:{}{}",
+                    ext_tag,
+                    MediaExtType::ProgramDateTime,
+                    program_date_time,
+                    nl
+                )?;
+            }
+
+            for (ext_type, value) in [
+                (MediaExtType::Album, &segment.album),
+                (MediaExtType::Artist, &segment.artist),
+                (MediaExtType::Genre, &segment.genre),
+            ] {
+                if let Some(value) = value {
+                    write!(output, "{}{}:{}{}", ext_tag, ext_type, value, nl)?;
+                }
+            }
+
+            if segment.gap {
+                write!(output, "{}-X-{}{}", ext_tag, MediaExtType::Gap, nl)?;
+            }
+
+            for part in &segment.parts {
+                write!(
+                    output,
+                    "{}-X-{}:{}{}",
+                    ext_tag,
+                    MediaExtType::Part,
+                    part,
+                    nl
+                )?;
+            }
+
+            write!(
+                output,
+                "{}{}:{:.3},{}{}",
+                ext_tag,
+                MediaExtType::Inf,
+                segment.duration,
+                segment.title.as_ref().unwrap_or(&"".to_owned()),
+                nl
+            )?;
+
+            if let Some(byte_range) = segment.byte_range {
+                write!(
+                    output,
+                    "{}-X-{}:{}{}",
+                    ext_tag,
+                    MediaExtType::ByteRange,
+                    byte_range,
+                    nl
+                )?;
+            }
+
+            write!(output, "{}{}", segment.uri, nl)?;
+        }
+
+        for ext_info in ext_infos {
+            ext_info.write_to(output, nl)?;
+        }
+
+        for preload_hint in &self.preload_hints {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::PreloadHint,
+                preload_hint,
+                nl
+            )?;
+        }
+
+        for rendition_report in &self.rendition_reports {
+            write!(
+                output,
+                "{}-X-{}:{}{}",
+                ext_tag,
+                MediaExtType::RenditionReport,
+                rendition_report,
+                nl
+            )?;
+        }
+
+        if self.end_list {
+            write!(output, "{}-X-{}{}", ext_tag, MediaExtType::EndList, nl)?;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites absolute segment URIs that share an origin with `base` into paths relative to
+    /// it. URIs on a different origin, and ones that are already relative, are left untouched.
+    pub fn relativize_uris(&mut self, base: &str) {
+        for segment in &mut self.media_segments {
+            if let Some(relative) = uri::relativize(base, &segment.uri) {
+                segment.uri = relative;
+            }
+        }
+    }
+
+    /// Replaces every `{$NAME}` reference in the segment, part, key and map URIs and in the
+    /// attribute values with the variable's value from `defines`.
+    ///
+    /// Returns [`M3U8ParserError::UndefinedVariable`] if a referenced variable isn't defined.
+    pub fn substitute_variables(&mut self) -> Result<(), M3U8ParserError<String>> {
+        let defines = &self.defines;
+        let substitute_in = |s: &mut String| -> Result<(), M3U8ParserError<String>> {
+            if s.contains("{$") {
+                *s = substitute(s, defines)?;
+            }
+
+            Ok(())
+        };
+
+        for segment in &mut self.media_segments {
+            substitute_in(&mut segment.uri)?;
+
+            if let Some(uri) = segment.key.as_mut().and_then(|k| k.uri.as_mut()) {
+                substitute_in(uri)?;
+            }
+
+            if let Some(map) = &mut segment.map {
+                substitute_in(&mut map.uri)?;
+            }
+
+            for part in &mut segment.parts {
+                substitute_in(&mut part.uri)?;
+            }
+        }
+
+        for preload_hint in &mut self.preload_hints {
+            substitute_in(&mut preload_hint.uri)?;
+        }
+
+        for rendition_report in &mut self.rendition_reports {
+            substitute_in(&mut rendition_report.uri)?;
+        }
+
+        for ext_info in &mut self.ext_infos {
+            substitute_attributes(&mut ext_info.attributes, defines)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn segment_mut(&mut self, index: usize) -> Option<&mut MediaSegment> {
+        self.media_segments.get_mut(index)
+    }
+
+    pub fn segments_mut(&mut self) -> impl Iterator<Item = &mut MediaSegment> {
+        self.media_segments.iter_mut()
+    }
+
+    /// Returns the media sequence number of the segment at `index`, or `None` if there's no
+    /// such segment.
+    pub fn segment_sequence(&self, index: usize) -> Option<u32> {
+        if index >= self.media_segments.len() {
+            return None;
+        }
+
+        self.media_sequence.checked_add(index as u32)
+    }
+
+    /// Returns the segment with the media sequence number `seq`, or `None` if it isn't in this
+    /// playlist.
+    pub fn segment_by_sequence(&self, seq: u32) -> Option<&MediaSegment> {
+        let index = seq.checked_sub(self.media_sequence)?;
+
+        self.media_segments.get(index as usize)
+    }
+
+    /// Returns the index of every segment preceded by an `#EXT-X-DISCONTINUITY`.
+    pub fn discontinuity_positions(&self) -> Vec<usize> {
+        self.media_segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.discontinuity)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Whether more segments may still be appended, i.e. there's no `#EXT-X-ENDLIST` and the
+    /// playlist should be polled for updates.
+    pub fn is_live(&self) -> bool {
+        !self.end_list
+    }
+
+    /// Sums every segment's duration using compensated summation, so long playlists don't
+    /// drift from accumulated floating-point error.
+    pub fn total_duration(&self) -> f64 {
+        compensated_sum(self.media_segments.iter().map(|s| s.duration))
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.media_segments.len()
+    }
+
+    /// Hashes the media sequence and the ordered segment URIs. A reloaded live playlist with
+    /// the same hash as the previous one has no new segments and can be skipped.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        self.media_sequence.hash(&mut hasher);
+
+        for segment in &self.media_segments {
+            segment.uri.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// Performs a cheap structural check of every segment URI, returning an issue for each
+    /// one that can't be a usable URI. Returns an empty `Vec` when all URIs look sane.
+    pub fn validate_uris(&self) -> Vec<UriIssue> {
+        self.media_segments
+            .iter()
+            .enumerate()
+            .filter_map(|(index, segment)| {
+                let segment_uri = segment.uri.as_str();
+
+                if segment_uri.is_empty() {
+                    Some(UriIssue::Empty { index })
+                } else if segment_uri.starts_with('#') {
+                    Some(UriIssue::LooksLikeTag { index })
+                } else if segment_uri.contains(char::is_whitespace) {
+                    Some(UriIssue::ContainsWhitespace { index })
+                } else if uri::authority(segment_uri) == Some("") {
+                    Some(UriIssue::MissingHost { index })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the segments are fragmented MP4, and so need an `#EXT-X-MAP` initialization
+    /// section. This is judged by the playlist already having a map or any segment URI
+    /// ending in `.mp4` or `.m4s`.
+    pub fn requires_map(&self) -> bool {
+        self.has_map() || self.media_segments.iter().any(|s| is_fmp4_uri(&s.uri))
+    }
+
+    fn has_map(&self) -> bool {
+        self.media_segments.iter().any(|s| s.map.is_some())
+    }
+
+    /// Flags every fragmented MP4 segment that has no `#EXT-X-MAP` to initialize it, a
+    /// packaging error that makes playback fail without any obvious cause.
+    pub fn validate_map(&self) -> Vec<MapIssue> {
+        if !self.requires_map() {
+            return Vec::new();
+        }
+
+        self.media_segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.map.is_none())
+            .map(|(index, _)| MapIssue::MissingMap { index })
+            .collect()
+    }
+
+    /// Checks the playlist against the rules of the HLS specification, returning every
+    /// violation found. Currently this checks that each segment duration, rounded to the
+    /// nearest integer, fits within `#EXT-X-TARGETDURATION`.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let errors = self
+            .media_segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.duration.round() > f64::from(self.target_duration))
+            .map(
+                |(index, segment)| ValidationError::SegmentExceedsTargetDuration {
+                    index,
+                    duration: segment.duration,
+                },
+            )
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Applies the fixes selected in `options` and returns what was changed.
+    pub fn normalize(&mut self, options: NormalizeOptions) -> Vec<NormalizeChange> {
+        let mut changes = Vec::new();
+
+        if options.realign_discontinuities {
+            let count = self.realign_discontinuities();
+
+            if count > 0 {
+                changes.push(NormalizeChange::DiscontinuitiesRealigned { count });
+            }
+        }
+
+        if options.remove_detached_discontinuities {
+            let count = self.ext_infos.len();
+
+            self.ext_infos
+                .retain(|e| e.ext_type != MediaExtType::Discontinuity);
+
+            let count = count - self.ext_infos.len();
+
+            if count > 0 {
+                changes.push(NormalizeChange::DetachedDiscontinuitiesRemoved { count });
+            }
+        }
+
+        if options.sort_attributes {
+            for (index, ext_info) in self.ext_infos.iter_mut().enumerate() {
+                let sorted = ext_info
+                    .attributes
+                    .keys()
+                    .zip(ext_info.attributes.keys().skip(1))
+                    .all(|(a, b)| a <= b);
+
+                if !sorted {
+                    ext_info.attributes.sort_keys();
+                    changes.push(NormalizeChange::AttributesSorted { index });
+                }
+            }
+        }
+
+        if options.resolve_byte_range_offsets {
+            for index in 1..self.media_segments.len() {
+                let (previous, segment) =
+                    (&self.media_segments[index - 1], &self.media_segments[index]);
+
+                let offset = match (previous.byte_range, segment.byte_range) {
+                    (
+                        Some(ByteRange {
+                            length,
+                            offset: Some(start),
+                        }),
+                        Some(ByteRange { offset: None, .. }),
+                    ) if previous.uri == segment.uri => start + length,
+                    _ => continue,
+                };
+
+                if let Some(ref mut byte_range) = self.media_segments[index].byte_range {
+                    byte_range.offset = Some(offset);
+                }
+
+                changes.push(NormalizeChange::ByteRangeOffsetResolved { index, offset });
+            }
+        }
+
+        if options.collapse_keys_and_maps {
+            let mut count = 0;
+
+            for index in 1..self.media_segments.len() {
+                let (previous, segment) =
+                    (&self.media_segments[index - 1], &self.media_segments[index]);
+
+                let key = match (&previous.key, &segment.key) {
+                    (Some(p), Some(k)) if p != k && p.is_equivalent(k) => Some(p.clone()),
+                    _ => None,
+                };
+                let map = match (&previous.map, &segment.map) {
+                    (Some(p), Some(m)) if p != m && p.is_equivalent(m) => Some(p.clone()),
+                    _ => None,
+                };
+
+                if key.is_some() {
+                    self.media_segments[index].key = key;
+                    count += 1;
+                }
+
+                if map.is_some() {
+                    self.media_segments[index].map = map;
+                    count += 1;
+                }
+            }
+
+            if count > 0 {
+                changes.push(NormalizeChange::KeysAndMapsCollapsed { count });
+            }
+        }
+
+        if options.recompute_target_duration {
+            let previous = self.target_duration;
+
+            self.recompute_target_duration();
+
+            if self.target_duration != previous {
+                changes.push(NormalizeChange::TargetDurationChanged {
+                    previous,
+                    current: self.target_duration,
+                });
+            }
+        }
+
+        changes
+    }
+
+    /// Replaces the `DISCONTINUITY` ext infos with one at the position of every segment that
+    /// has `discontinuity` set, returning how many were added or removed.
+    fn realign_discontinuities(&mut self) -> usize {
+        let current = self
+            .ext_infos
+            .iter()
+            .filter(|e| e.ext_type == MediaExtType::Discontinuity)
+            .map(|e| e.position)
+            .collect::<Vec<_>>();
+        let expected = self.discontinuity_positions();
+
+        let missing = expected.iter().filter(|p| !current.contains(p)).count();
+        let extra = current.iter().filter(|p| !expected.contains(p)).count();
+        let duplicates = current.len() - (expected.len() - missing) - extra;
+
+        let count = missing + extra + duplicates;
+
+        if count == 0 {
+            return 0;
+        }
+
+        self.ext_infos
+            .retain(|e| e.ext_type != MediaExtType::Discontinuity);
+
+        for position in expected {
+            // Before the other tags at the same position, as the parser would have read it.
+            let index = self
+                .ext_infos
+                .iter()
+                .position(|e| e.position >= position)
+                .unwrap_or(self.ext_infos.len());
+
+            self.ext_infos.insert(
+                index,
+                MediaExtInfo {
+                    ext_type: MediaExtType::Discontinuity,
+                    attributes: IndexMap::new(),
+                    position,
+                },
+            );
+        }
+
+        count
+    }
+
+    /// Sets `target_duration` to the smallest value every segment duration, rounded to the
+    /// nearest integer, fits within.
+    pub fn recompute_target_duration(&mut self) {
+        self.target_duration = self.required_target_duration();
+    }
+
+    fn required_target_duration(&self) -> u8 {
+        self.media_segments
+            .iter()
+            .map(|s| s.duration.round() as u8)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Checks that this playlist's `MEDIA-SEQUENCE` and `DISCONTINUITY-SEQUENCE` moved on
+    /// consistently from an earlier snapshot of the same live playlist: the discontinuity
+    /// sequence must grow by exactly the number of discontinuities that scrolled off.
+    pub fn validate_sequence_consistency(&self, previous: &MediaList) -> Vec<SequenceIssue> {
+        if self.media_sequence < previous.media_sequence {
+            return vec![SequenceIssue::MediaSequenceDecreased {
+                previous: previous.media_sequence,
+                current: self.media_sequence,
+            }];
+        }
+
+        let removed = (self.media_sequence - previous.media_sequence) as usize;
+
+        if removed > previous.media_segments.len() {
+            return vec![SequenceIssue::SnapshotTooOld {
+                missing_segments: (removed - previous.media_segments.len()) as u32,
+            }];
+        }
+
+        let removed_discontinuities = previous.media_segments[..removed]
+            .iter()
+            .filter(|s| s.discontinuity)
+            .count() as u64;
+
+        let expected = u64::from(previous.discontinuity_sequence) + removed_discontinuities;
+
+        let expected = match u32::try_from(expected) {
+            Ok(expected) => expected,
+            Err(_) => {
+                return vec![SequenceIssue::DiscontinuitySequenceOverflowed {
+                    previous: previous.discontinuity_sequence,
+                    removed_discontinuities,
+                }]
+            }
+        };
+
+        if self.discontinuity_sequence != expected {
+            return vec![SequenceIssue::DiscontinuitySequenceMismatch {
+                expected,
+                actual: self.discontinuity_sequence,
+            }];
+        }
+
+        Vec::new()
+    }
+
+    /// Appends the segments of a later snapshot of the same live playlist that come after this
+    /// list's last segment. Segments are matched by media sequence number rather than URI,
+    /// since signed URIs can change between snapshots.
+    ///
+    /// `MEDIA-SEQUENCE` and `DISCONTINUITY-SEQUENCE` keep describing the first segment held,
+    /// unless `newer` starts after this list's last segment. The segments in between were
+    /// missed and can't be represented, so the list then restarts from `newer`'s segments, with
+    /// `DISCONTINUITY-SEQUENCE` counting the discontinuities dropped along with the old ones.
+    pub fn merge(&mut self, newer: MediaList<'a>) {
+        let next_seq = u64::from(self.media_sequence) + self.media_segments.len() as u64;
+        // Segments dropped by an `EXT-X-SKIP` delta update aren't listed.
+        let newer_first_seq =
+            u64::from(newer.media_sequence) + newer.skip.as_ref().map_or(0, |s| s.skipped_segments);
+
+        if self.media_segments.is_empty() || newer_first_seq > next_seq {
+            // Every held segment is dropped, so the discontinuities among them are counted in,
+            // unless `newer` already accounts for more.
+            let dropped_discontinuities = self
+                .media_segments
+                .iter()
+                .filter(|s| s.discontinuity)
+                .count() as u64;
+            let discontinuity_sequence = (u64::from(self.discontinuity_sequence)
+                + dropped_discontinuities)
+                .max(u64::from(newer.discontinuity_sequence));
+
+            let saturate = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+
+            self.media_sequence = saturate(newer_first_seq);
+            self.discontinuity_sequence = saturate(discontinuity_sequence);
+            self.media_segments.clear();
+        }
+
+        let next_seq = u64::from(self.media_sequence) + self.media_segments.len() as u64;
+        let already_held = next_seq.saturating_sub(newer_first_seq) as usize;
+
+        self.extend(newer.media_segments.into_iter().skip(already_held));
+        self.target_duration = self.target_duration.max(newer.target_duration);
+        self.end_list = newer.end_list;
+        self.preload_hints = newer.preload_hints;
+        self.rendition_reports = newer.rendition_reports;
+    }
+
+    /// Returns a standalone media list holding the segments with media sequence numbers in
+    /// `start_seq..start_seq + count`, clamped to the segments this list actually has.
+    /// `MEDIA-SEQUENCE` and `DISCONTINUITY-SEQUENCE` are adjusted so the window describes the
+    /// same segments as the original, and every segment keeps the context it was parsed with.
+    pub fn window(&self, start_seq: u64, count: usize) -> MediaList<'a> {
+        let first_seq = u64::from(self.media_sequence);
+        let end_seq = start_seq.saturating_add(count as u64);
+
+        let len = self.media_segments.len();
+        let to_index = |seq: u64| (seq.saturating_sub(first_seq) as usize).min(len);
+        let (start, end) = (
+            to_index(start_seq),
+            to_index(end_seq).max(to_index(start_seq)),
+        );
+
+        let skipped_discontinuities = self.media_segments[..start]
+            .iter()
+            .filter(|s| s.discontinuity)
+            .count() as u64;
+
+        // Both sequences saturate rather than wrap for a list numbered right up to `u32::MAX`.
+        let saturate = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+
+        MediaList {
+            version: self.version,
+            playlist_type: self.playlist_type,
+            target_duration: self.target_duration,
+            media_sequence: saturate(first_seq + start as u64),
+            discontinuity_sequence: saturate(
+                u64::from(self.discontinuity_sequence) + skipped_discontinuities,
+            ),
+            media_segments: self.media_segments[start..end].to_vec(),
+            // Tags outside the window are kept at its nearest edge.
+            ext_infos: self
+                .ext_infos
+                .iter()
+                .filter(|e| e.ext_type != MediaExtType::Discontinuity)
+                .map(|e| MediaExtInfo {
+                    position: e.position.clamp(start, end) - start,
+                    ..e.clone()
+                })
+                .collect(),
+            end_list: self.end_list && end == len,
+            independent_segments: self.independent_segments,
+            iframes_only: self.iframes_only,
+            start: self.start,
+            server_control: self.server_control,
+            defines: self.defines.clone(),
+            // Hints describe what follows the last segment, so they only apply to the tail.
+            preload_hints: if end == len {
+                self.preload_hints.clone()
+            } else {
+                Vec::new()
+            },
+            rendition_reports: self.rendition_reports.clone(),
+            // The skipped segments come before the first one, so only the head keeps them.
+            skip: self.skip.clone().filter(|_| start == 0),
+            line_ending: self.line_ending,
+            header_attributes: self.header_attributes.clone(),
+        }
+    }
+
+    /// Finds the ad breaks in this playlist, ordered by their first segment.
+    ///
+    /// Breaks are delimited by `#EXT-X-CUE-OUT` and the next `#EXT-X-CUE-IN`, or described by
+    /// a `DATERANGE` carrying `SCTE35-OUT` or Twitch's `twitch-stitched-ad` class. A `DATERANGE`
+    /// break starts at the segment whose `PROGRAM-DATE-TIME` equals its `START-DATE` and spans
+    /// as many segments as its `DURATION` covers. A break still open at the end of the playlist
+    /// ends after the last segment.
+    pub fn ad_breaks(&self) -> Vec<AdBreak> {
+        let mut ad_breaks = Vec::new();
+        let mut open: Option<AdBreak> = None;
+
+        for (index, segment) in self.media_segments.iter().enumerate() {
+            if segment.cue_in || segment.cue_out.is_some() {
+                if let Some(mut ad_break) = open.take() {
+                    ad_break.end_segment = index;
+                    ad_breaks.push(ad_break);
+                }
+            }
+
+            if let Some(ref cue_out) = segment.cue_out {
+                open = Some(AdBreak {
+                    start_segment: index,
+                    end_segment: index,
+                    duration: cue_out.duration,
+                });
+            }
+        }
+
+        if let Some(mut ad_break) = open {
+            ad_break.end_segment = self.media_segments.len();
+            ad_breaks.push(ad_break);
+        }
+
+        for ext_info in &self.ext_infos {
+            if let Some(ad_break) = self.date_range_ad_break(ext_info) {
+                if ad_breaks
+                    .iter()
+                    .all(|b| b.start_segment != ad_break.start_segment)
+                {
+                    ad_breaks.push(ad_break);
+                }
+            }
+        }
+
+        ad_breaks.sort_by_key(|b| b.start_segment);
+
+        ad_breaks
+    }
+
+    fn date_range_ad_break(&self, ext_info: &MediaExtInfo) -> Option<AdBreak> {
+        let attribute = |key| ext_info.attributes.get(key).map(|v| unquote(v));
+
+        if ext_info.ext_type != MediaExtType::DateRange
+            || (attribute("SCTE35-OUT").is_none()
+                && attribute("CLASS") != Some("twitch-stitched-ad"))
+        {
+            return None;
+        }
+
+        let start_date = attribute("START-DATE")?;
+        let duration = attribute("DURATION")
+            .map(|d| d.parse::<f64>())
+            .transpose()
+            .ok()?;
+
+        let start_segment = self
+            .media_segments
+            .iter()
+            .position(|s| s.program_date_time.as_deref() == Some(start_date))?;
+
+        let mut end_segment = start_segment + 1;
+
+        if let Some(duration) = duration {
+            let mut covered = self.media_segments[start_segment].duration;
+
+            // Allow for the rounding of EXTINF durations to milliseconds.
+            while covered < duration - 0.001 && end_segment < self.media_segments.len() {
+                covered += self.media_segments[end_segment].duration;
+                end_segment += 1;
+            }
+        }
+
+        Some(AdBreak {
+            start_segment,
+            end_segment,
+            duration,
+        })
+    }
+
+    /// Returns a copy of this playlist's header, i.e. everything but the segments and the
+    /// tags tied to them, ready to have new segments pushed onto it.
+    pub fn header_template(&self) -> MediaList<'a> {
+        MediaList {
+            version: self.version,
+            playlist_type: self.playlist_type,
+            target_duration: self.target_duration,
+            media_sequence: self.media_sequence,
+            discontinuity_sequence: self.discontinuity_sequence,
+            media_segments: Vec::new(),
+            ext_infos: self
+                .ext_infos
+                .iter()
+                .filter(|e| {
+                    e.ext_type != MediaExtType::DateRange
+                        && e.ext_type != MediaExtType::Discontinuity
+                })
+                .map(|e| MediaExtInfo {
+                    position: 0,
+                    ..e.clone()
+                })
+                .collect(),
+            end_list: self.end_list,
+            independent_segments: self.independent_segments,
+            iframes_only: self.iframes_only,
+            start: self.start,
+            server_control: self.server_control,
+            defines: self.defines.clone(),
+            preload_hints: Vec::new(),
+            rendition_reports: Vec::new(),
+            skip: None,
+            line_ending: self.line_ending,
+            header_attributes: self.header_attributes.clone(),
+        }
+    }
+
+    /// Builds a `STREAM-INF` entry that references this media list at `uri`, for use in a
+    /// master playlist, or an `I-FRAME-STREAM-INF` one if the list is I-frame only.
+    /// Only `BANDWIDTH`, `URI` and, when every segment has an `#EXT-X-BITRATE`,
+    /// `AVERAGE-BANDWIDTH` are set. `RESOLUTION` and `CODECS` are never derived, since they're
+    /// only known from the media itself and a map just names its initialization section, so
+    /// they're left for the caller to insert.
+    pub fn as_stream_inf<'b>(&self, uri: &'b str, bandwidth: u64) -> PlaylistExtInfo<'b> {
+        let mut attributes = Attributes::new();
+
+        attributes.insert("BANDWIDTH".into(), bandwidth.to_string().into());
+
+        if let Some(average_bandwidth) = self.average_bandwidth() {
+            attributes.insert(
+                "AVERAGE-BANDWIDTH".into(),
+                average_bandwidth.to_string().into(),
+            );
+        }
+
+        // An I-frame stream has no URI line, so it's a quoted attribute instead.
+        if self.iframes_only {
+            attributes.insert("URI".into(), format!("\"{}\"", uri).into());
+
+            PlaylistExtInfo {
+                ext_type: PlaylistExtType::IFrameStreamInf,
+                attributes,
+            }
+        } else {
+            attributes.insert("URI".into(), uri.into());
+
+            PlaylistExtInfo {
+                ext_type: PlaylistExtType::StreamInf,
+                attributes,
+            }
+        }
+    }
+
+    /// The duration-weighted average of the segments' bitrates in bits per second, if every
+    /// segment has one.
+    fn average_bandwidth(&self) -> Option<u64> {
+        let mut bits = 0.0;
+        let mut duration = 0.0;
+
+        for segment in &self.media_segments {
+            // `#EXT-X-BITRATE` is in kilobits per second.
+            bits += f64::from(segment.bitrate?) * 1000.0 * segment.duration;
+            duration += segment.duration;
+        }
+
+        if duration > 0.0 {
+            Some((bits / duration).round() as u64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Renders the media list exactly as [`MediaList::save`] writes it.
+impl fmt::Display for MediaList<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write_to(f, self.line_ending.as_str())
+    }
+}
+
+/// Parses a media playlist with [`read_media_list`], copying it so it doesn't borrow `s`.
+impl FromStr for OwnedMediaList {
+    type Err = M3U8ParserError<String>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        read_media_list(s)
+            .map(MediaList::into_owned)
+            .map_err(M3U8ParserError::into_owned)
+    }
+}
+
+/// Parses a media playlist with [`read_media_list`], borrowing from `data`.
+impl<'a> TryFrom<&'a str> for MediaList<'a> {
+    type Error = M3U8ParserError<&'a str>;
+
+    fn try_from(data: &'a str) -> Result<Self, Self::Error> {
+        read_media_list(data)
+    }
+}
+
+/// Renders a media list with an overridden line ending, so [`MediaList::save_with`] can
+/// stream the same output as `Display` straight into a `Write`.
+struct MediaListDisplay<'a, 'b> {
+    media_list: &'b MediaList<'a>,
+    nl: &'b str,
+}
+
+impl fmt::Display for MediaListDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.media_list.write_to(f, self.nl)
+    }
+}
+
+fn is_fmp4_uri(uri: &str) -> bool {
+    let path = uri
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(uri)
+        .to_ascii_lowercase();
+
+    path.ends_with(".mp4") || path.ends_with(".m4s")
+}
+
+#[cfg(feature = "serde")]
+impl<'a> MediaList<'a> {
+    /// Builds a JSON tree of the header fields, ext infos and segments, including the typed
+    /// per-segment context such as keys and maps, for logging and debugging.
+    pub fn to_json_value(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let ext_infos = self
+            .ext_infos
+            .iter()
+            .map(|e| {
+                json!({
+                    "type": format!("{}{}", e.ext_type.tag_prefix(), e.ext_type),
+                    "attributes": e
+                        .attributes
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), json!(v)))
+                        .collect::<serde_json::Map<_, _>>(),
+                    "position": e.position,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let segments = self
+            .media_segments
+            .iter()
+            .map(|s| {
+                json!({
+                    "duration": s.duration,
+                    "title": s.title,
+                    "uri": s.uri,
+                    "program_date_time": s.program_date_time,
+                    "discontinuity": s.discontinuity,
+                    "album": s.album,
+                    "artist": s.artist,
+                    "genre": s.genre,
+                    "cue_out": s.cue_out.as_ref().map(|c| json!({ "duration": c.duration })),
+                    "cue_in": s.cue_in,
+                    "key": s.key.as_ref().map(|k| json!({
+                        "method": k.method.to_string(),
+                        "uri": k.uri,
+                        "iv": k.iv,
+                        "keyformat": k.keyformat,
+                        "keyformatversions": k.keyformatversions,
+                    })),
+                    "map": s.map.as_ref().map(|m| json!({
+                        "uri": m.uri,
+                        "byte_range": m.byte_range.map(|b| json!({
+                            "length": b.length,
+                            "offset": b.offset,
+                        })),
+                    })),
+                    "gap": s.gap,
+                    "bitrate": s.bitrate,
+                    "byte_range": s.byte_range.map(|b| json!({
+                        "length": b.length,
+                        "offset": b.offset,
+                    })),
+                    "parts": s.parts.iter().map(|p| json!({
+                        "duration": p.duration,
+                        "uri": p.uri,
+                        "independent": p.independent,
+                        "byte_range": p.byte_range.map(|b| json!({
+                            "length": b.length,
+                            "offset": b.offset,
+                        })),
+                    })).collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "version": self.version,
+            "playlist_type": self.playlist_type.map(|t| t.to_string()),
+            "target_duration": self.target_duration,
+            "media_sequence": self.media_sequence,
+            "discontinuity_sequence": self.discontinuity_sequence,
+            "end_list": self.end_list,
+            "independent_segments": self.independent_segments,
+            "iframes_only": self.iframes_only,
+            "start": self.start.map(|s| json!({
+                "time_offset": s.time_offset,
+                "precise": s.precise,
+            })),
+            "server_control": self.server_control.map(|c| json!({
+                "can_skip_until": c.can_skip_until,
+                "can_skip_dateranges": c.can_skip_dateranges,
+                "hold_back": c.hold_back,
+                "part_hold_back": c.part_hold_back,
+                "can_block_reload": c.can_block_reload,
+            })),
+            "defines": self.defines,
+            "preload_hints": self.preload_hints.iter().map(|h| json!({
+                "type": h.hint_type,
+                "uri": h.uri,
+                "byterange_start": h.byterange_start,
+                "byterange_length": h.byterange_length,
+            })).collect::<Vec<_>>(),
+            "rendition_reports": self.rendition_reports.iter().map(|r| json!({
+                "uri": r.uri,
+                "last_msn": r.last_msn,
+                "last_part": r.last_part,
+            })).collect::<Vec<_>>(),
+            "skip": self.skip.as_ref().map(|s| json!({
+                "skipped_segments": s.skipped_segments,
+                "recently_removed_dateranges": s.recently_removed_dateranges,
+            })),
+            "header_attributes": self
+                .header_attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), json!(v)))
+                .collect::<serde_json::Map<_, _>>(),
+            "ext_infos": ext_infos,
+            "segments": segments,
+        })
+    }
+}
+
+/// Neumaier's variant of Kahan summation.
+fn compensated_sum<I: IntoIterator<Item = f64>>(values: I) -> f64 {
+    let mut sum = 0.0;
+    let mut compensation = 0.0;
+
+    for value in values {
+        let t = sum + value;
+
+        if f64::abs(sum) >= f64::abs(value) {
+            compensation += (sum - t) + value;
+        } else {
+            compensation += (value - t) + sum;
+        }
+
+        sum = t;
+    }
+
+    sum + compensation
+}
+
+impl<'a> Extend<MediaSegment> for MediaList<'a> {
+    /// Appends the segments, raising `target_duration` if any of them no longer fit within it.
+    fn extend<T: IntoIterator<Item = MediaSegment>>(&mut self, iter: T) {
+        self.media_segments.extend(iter);
+        self.target_duration = self.target_duration.max(self.required_target_duration());
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UriIssue {
+    Empty {
+        index: usize,
+    },
+    /// The URI line is actually a tag, which usually means a segment's URI line is missing.
+    LooksLikeTag {
+        index: usize,
+    },
+    ContainsWhitespace {
+        index: usize,
+    },
+    MissingHost {
+        index: usize,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MapIssue {
+    /// A fragmented MP4 segment isn't preceded by an `#EXT-X-MAP`.
+    MissingMap { index: usize },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// The segment's duration, rounded to the nearest integer, is longer than the target
+    /// duration.
+    SegmentExceedsTargetDuration { index: usize, duration: f64 },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SequenceIssue {
+    MediaSequenceDecreased {
+        previous: u32,
+        current: u32,
+    },
+    /// More segments were removed than the previous snapshot contained, so the removed
+    /// discontinuities can't be counted.
+    SnapshotTooOld {
+        missing_segments: u32,
+    },
+    DiscontinuitySequenceMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// The discontinuity sequence should have grown past `u32::MAX`, which it can't be.
+    DiscontinuitySequenceOverflowed {
+        previous: u32,
+        removed_discontinuities: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaExtInfo<'a> {
+    pub ext_type: MediaExtType,
+    pub attributes: Attributes<'a>,
+    /// The number of segments before the tag, so `save` can write it back between the same
+    /// segments. Tags past the last segment are written after it.
+    pub position: usize,
+}
+
+impl<'a> MediaExtInfo<'a> {
+    pub fn into_owned(self) -> MediaExtInfo<'static> {
+        MediaExtInfo {
+            ext_type: self.ext_type,
+            attributes: into_owned_attributes(self.attributes),
+            position: self.position,
+        }
+    }
+
+    /// Sets the attribute to `value`, quoting it if the value it replaces was quoted. A new
+    /// attribute is written as given, so a quoted-string should include its quotes.
+    pub fn set_attr(&mut self, key: &str, value: impl Into<String>) {
+        set_attribute(&mut self.attributes, key, value.into());
+    }
+
+    fn write_to<W: fmt::Write>(&self, output: &mut W, nl: &str) -> fmt::Result {
+        match &self.ext_type {
+            // These are written alongside the segment they apply to.
+            MediaExtType::Inf
+            | MediaExtType::ProgramDateTime
+            | MediaExtType::Discontinuity
+            | MediaExtType::Album
+            | MediaExtType::Artist
+            | MediaExtType::Genre
+            | MediaExtType::CueOut
+            | MediaExtType::CueIn
+            | MediaExtType::Key
+            | MediaExtType::Map
+            | MediaExtType::Gap
+            | MediaExtType::Bitrate
+            | MediaExtType::ByteRange
+            | MediaExtType::Part
+            | MediaExtType::PreloadHint
+            | MediaExtType::RenditionReport
+            | MediaExtType::Skip
+            | MediaExtType::ServerControl => Ok(()),
+            _ => {
+                write!(
+                    output,
+                    "#EXT{}{}",
+                    self.ext_type.tag_prefix(),
+                    self.ext_type
+                )?;
+
+                if !self.attributes.is_empty() {
+                    write!(output, ":{}", rejoin_attributes(&self.attributes))?;
+                }
+
+                write!(output, "{}", nl)
+            }
+        }
+    }
+
+    /// Returns the attribute with any balanced surrounding quotes removed and escaped quotes
+    /// unescaped, leaving enumerated and numeric values as they are. `attributes` keeps the
+    /// raw values for round-tripping.
+    pub fn attr(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.attributes.get(key).map(|v| attribute_value(v))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MediaExtType {
+    Version,
+    TargetDuration,
+    MediaSequence,
+    DiscontinuitySequence,
+    PlaylistType,
+    DateRange,
+    Discontinuity,
+    Inf,
+    ProgramDateTime,
+    EndList,
+    IndependentSegments,
+    Start,
+    Album,
+    Artist,
+    Genre,
+    CueOut,
+    CueIn,
+    Key,
+    Map,
+    Gap,
+    Bitrate,
+    ByteRange,
+    IFramesOnly,
+    Part,
+    PreloadHint,
+    RenditionReport,
+    Skip,
+    ServerControl,
+    Define,
+    /// An unrecognised `#EXT-X-` tag, holding the name after `#EXT-X-`.
+    Unknown(String),
+    /// An unrecognised `#EXT` tag without the `-X-` prefix, holding the name after `#EXT`.
+    UnknownExt(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaSegment {
+    pub duration: f64,
+    pub title: Option<String>,
+    pub uri: String,
+    pub program_date_time: Option<String>,
+    pub discontinuity: bool,
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    pub genre: Option<String>,
+    /// Set when an `#EXT-X-CUE-OUT` starting an ad break precedes this segment.
+    pub cue_out: Option<CueOut>,
+    /// Set when an `#EXT-X-CUE-IN` ending an ad break precedes this segment.
+    pub cue_in: bool,
+    /// The `#EXT-X-KEY` in effect for this segment, or `None` if it isn't encrypted.
+    pub key: Option<Key>,
+    /// The `#EXT-X-MAP` initialization section in effect for this segment.
+    pub map: Option<MapInfo>,
+    /// Set when an `#EXT-X-GAP` marks this segment as unavailable.
+    pub gap: bool,
+    /// The `#EXT-X-BITRATE` in effect for this segment, in kbit/s.
+    pub bitrate: Option<u32>,
+    /// Set when an `#EXT-X-BYTERANGE` limits the segment to part of the resource at `uri`.
+    pub byte_range: Option<ByteRange>,
+    /// The `#EXT-X-PART` partial segments making up this segment. Parts after the last
+    /// `#EXTINF` belong to a segment that isn't complete yet and aren't kept.
+    pub parts: Vec<Part>,
+}
+
+impl MediaSegment {
+    /// Creates a segment with only a duration and URI, leaving every tag unset.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use m3u8_parser::MediaSegment;
+    ///
+    /// let segment = MediaSegment::new(Duration::from_millis(6006), "segment0.ts")
+    ///     .with_title("Intro")
+    ///     .with_program_date_time("2020-11-18T14:12:40.956Z");
+    ///
+    /// assert_eq!(segment.duration, 6.006);
+    /// assert_eq!(segment.uri, "segment0.ts");
+    /// assert_eq!(segment.title.as_deref(), Some("Intro"));
+    /// assert!(segment.key.is_none());
+    /// ```
+    pub fn new(duration: Duration, uri: impl Into<String>) -> Self {
+        MediaSegment {
+            duration: duration.as_secs_f64(),
+            title: None,
+            uri: uri.into(),
+            program_date_time: None,
+            discontinuity: false,
+            album: None,
+            artist: None,
+            genre: None,
+            cue_out: None,
+            cue_in: false,
+            key: None,
+            map: None,
+            gap: false,
+            bitrate: None,
+            byte_range: None,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Returns `duration` as a `Duration`, or `Duration::ZERO` if it's negative, not finite or
+    /// too large to represent.
+    pub fn duration_as_std(&self) -> Duration {
+        Duration::try_from_secs_f64(self.duration).unwrap_or(Duration::ZERO)
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_program_date_time(mut self, program_date_time: impl Into<String>) -> Self {
+        self.program_date_time = Some(program_date_time.into());
+        self
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl MediaSegment {
+    /// Parses `program_date_time` as an ISO 8601 date and time, returning `None` when it's
+    /// absent or malformed. The offset may be written with or without a colon.
+    pub fn program_date_time_parsed(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        let program_date_time = self.program_date_time.as_deref()?;
+
+        chrono::DateTime::parse_from_rfc3339(program_date_time)
+            .or_else(|_| {
+                chrono::DateTime::parse_from_str(program_date_time, "%Y-%m-%dT%H:%M:%S%.f%z")
+            })
+            .ok()
+    }
+}
+
+/// A sub-range of a resource, written as `<length>[@<offset>]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ByteRange {
+    pub length: u64,
+    pub offset: Option<u64>,
+}
+
+impl ByteRange {
+    fn parse(s: &str) -> Result<Self, std::num::ParseIntError> {
+        let mut parts = s.splitn(2, '@');
+
+        Ok(ByteRange {
+            length: parts.next().unwrap_or("").parse()?,
+            offset: parts.next().map(|o| o.parse()).transpose()?,
+        })
+    }
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.length)?;
+
+        if let Some(offset) = self.offset {
+            write!(f, "@{}", offset)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A parsed `#EXT-X-MAP`, naming the initialization section for the segments after it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapInfo {
+    pub uri: String,
+    pub byte_range: Option<ByteRange>,
+}
+
+impl MapInfo {
+    /// Whether both maps name the same bytes, treating an absent offset as zero.
+    fn is_equivalent(&self, other: &MapInfo) -> bool {
+        let range = |m: &MapInfo| m.byte_range.map(|b| (b.length, b.offset.unwrap_or(0)));
+
+        self.uri == other.uri && range(self) == range(other)
+    }
+}
+
+impl fmt::Display for MapInfo {
+    /// Writes the map as an attribute list, quoting `URI` and `BYTERANGE`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "URI=\"{}\"", self.uri)?;
+
+        if let Some(ref byte_range) = self.byte_range {
+            write!(f, ",BYTERANGE=\"{}\"", byte_range)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A Low-Latency HLS resource that will be needed after the current segments, declared by
+/// `#EXT-X-PRELOAD-HINT`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PreloadHint {
+    /// The `TYPE` attribute, either `PART` or `MAP`.
+    pub hint_type: String,
+    pub uri: String,
+    pub byterange_start: Option<u64>,
+    pub byterange_length: Option<u64>,
+}
+
+impl PreloadHint {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        let number = |key| attributes.get(key).map(|v| v.parse::<u64>()).transpose();
+
+        Ok(PreloadHint {
+            hint_type: attributes.get("TYPE").map_or("", |t| t.as_ref()).to_owned(),
+            uri: attributes.get("URI").map_or("", |u| unquote(u)).to_owned(),
+            byterange_start: number("BYTERANGE-START")?,
+            byterange_length: number("BYTERANGE-LENGTH")?,
+        })
+    }
+}
+
+impl fmt::Display for PreloadHint {
+    /// Writes the hint as an attribute list, quoting `URI`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "TYPE={},URI=\"{}\"", self.hint_type, self.uri)?;
+
+        if let Some(byterange_start) = self.byterange_start {
+            write!(f, ",BYTERANGE-START={}", byterange_start)?;
+        }
+
+        if let Some(byterange_length) = self.byterange_length {
+            write!(f, ",BYTERANGE-LENGTH={}", byterange_length)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How a server supports live playlist reloads, as given by `#EXT-X-SERVER-CONTROL`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerControl {
+    /// How far from the live edge, in seconds, a delta update with `#EXT-X-SKIP` can be
+    /// requested.
+    pub can_skip_until: Option<f64>,
+    pub can_skip_dateranges: bool,
+    pub hold_back: Option<f64>,
+    pub part_hold_back: Option<f64>,
+    pub can_block_reload: bool,
+}
+
+impl ServerControl {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        let seconds = |key| attributes.get(key).map(|v| v.parse::<f64>()).transpose();
+        let flag = |key| attributes.get(key).is_some_and(|v| v == "YES");
+
+        Ok(ServerControl {
+            can_skip_until: seconds("CAN-SKIP-UNTIL")?,
+            can_skip_dateranges: flag("CAN-SKIP-DATERANGES"),
+            hold_back: seconds("HOLD-BACK")?,
+            part_hold_back: seconds("PART-HOLD-BACK")?,
+            can_block_reload: flag("CAN-BLOCK-RELOAD"),
+        })
+    }
+}
+
+impl fmt::Display for ServerControl {
+    /// Writes the server control as an attribute list, leaving out absent and `NO` values.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut attributes = Vec::new();
+
+        if let Some(can_skip_until) = self.can_skip_until {
+            attributes.push(format!("CAN-SKIP-UNTIL={}", can_skip_until));
+        }
+
+        if self.can_skip_dateranges {
+            attributes.push("CAN-SKIP-DATERANGES=YES".to_owned());
+        }
+
+        if let Some(hold_back) = self.hold_back {
+            attributes.push(format!("HOLD-BACK={}", hold_back));
+        }
+
+        if let Some(part_hold_back) = self.part_hold_back {
+            attributes.push(format!("PART-HOLD-BACK={}", part_hold_back));
+        }
+
+        if self.can_block_reload {
+            attributes.push("CAN-BLOCK-RELOAD=YES".to_owned());
+        }
+
+        write!(f, "{}", attributes.join(","))
+    }
+}
+
+/// Marks a delta update, where `#EXT-X-SKIP` replaces the segments a client already has.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Skip {
+    pub skipped_segments: u64,
+    /// The tab-separated IDs of the date ranges removed since the skipped segments.
+    pub recently_removed_dateranges: Option<String>,
+}
+
+impl Skip {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        let skipped_segments = attributes
+            .get("SKIPPED-SEGMENTS")
+            .map_or("", |s| s.as_ref())
+            .parse::<u64>()?;
+
+        Ok(Skip {
+            skipped_segments,
+            recently_removed_dateranges: attributes
+                .get("RECENTLY-REMOVED-DATERANGES")
+                .map(|d| unquote(d).to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for Skip {
+    /// Writes the skip as an attribute list, quoting `RECENTLY-REMOVED-DATERANGES`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "SKIPPED-SEGMENTS={}", self.skipped_segments)?;
+
+        if let Some(ref recently_removed_dateranges) = self.recently_removed_dateranges {
+            write!(
+                f,
+                ",RECENTLY-REMOVED-DATERANGES=\"{}\"",
+                recently_removed_dateranges
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The latest segment and part of another rendition, declared by `#EXT-X-RENDITION-REPORT` so
+/// a Low-Latency HLS player can switch without reloading that rendition first.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenditionReport {
+    pub uri: String,
+    /// The media sequence number of the rendition's last segment.
+    pub last_msn: Option<u64>,
+    /// The index of the rendition's last part within its last segment.
+    pub last_part: Option<u32>,
+}
+
+impl RenditionReport {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        Ok(RenditionReport {
+            uri: attributes.get("URI").map_or("", |u| unquote(u)).to_owned(),
+            last_msn: attributes
+                .get("LAST-MSN")
+                .map(|m| m.parse::<u64>())
+                .transpose()?,
+            last_part: attributes
+                .get("LAST-PART")
+                .map(|p| p.parse::<u32>())
+                .transpose()?,
+        })
+    }
+}
+
+impl fmt::Display for RenditionReport {
+    /// Writes the report as an attribute list, quoting `URI`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "URI=\"{}\"", self.uri)?;
+
+        if let Some(last_msn) = self.last_msn {
+            write!(f, ",LAST-MSN={}", last_msn)?;
+        }
+
+        if let Some(last_part) = self.last_part {
+            write!(f, ",LAST-PART={}", last_part)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A Low-Latency HLS partial segment, declared by `#EXT-X-PART`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Part {
+    pub duration: f64,
+    pub uri: String,
+    /// Whether the part can be decoded without the parts before it.
+    pub independent: bool,
+    pub byte_range: Option<ByteRange>,
+}
+
+impl Part {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        let duration = attributes
+            .get("DURATION")
+            .map_or("", |d| d.as_ref())
+            .parse::<f64>()?;
+
+        let byte_range = attributes
+            .get("BYTERANGE")
+            .map(|b| ByteRange::parse(unquote(b)))
+            .transpose()?;
+
+        Ok(Part {
+            duration,
+            uri: attributes.get("URI").map_or("", |u| unquote(u)).to_owned(),
+            independent: attributes.get("INDEPENDENT").is_some_and(|i| i == "YES"),
+            byte_range,
+        })
+    }
+}
+
+impl fmt::Display for Part {
+    /// Writes the part as an attribute list, quoting `URI` and `BYTERANGE`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "DURATION={},URI=\"{}\"", self.duration, self.uri)?;
+
+        if self.independent {
+            write!(f, ",INDEPENDENT=YES")?;
+        }
+
+        if let Some(ref byte_range) = self.byte_range {
+            write!(f, ",BYTERANGE=\"{}\"", byte_range)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyMethod {
+    None,
+    Aes128,
+    SampleAes,
+    SampleAesCtr,
+}
+
+impl fmt::Display for KeyMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyMethod::None => write!(f, "NONE"),
+            KeyMethod::Aes128 => write!(f, "AES-128"),
+            KeyMethod::SampleAes => write!(f, "SAMPLE-AES"),
+            KeyMethod::SampleAesCtr => write!(f, "SAMPLE-AES-CTR"),
+        }
+    }
+}
+
+/// A parsed `#EXT-X-KEY`, with quotes removed from its quoted-string attributes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key {
+    pub method: KeyMethod,
+    pub uri: Option<String>,
+    pub iv: Option<String>,
+    pub keyformat: Option<String>,
+    pub keyformatversions: Option<String>,
+}
+
+/// The key system a [`Key`] is meant for, derived from its `KEYFORMAT`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySystem {
+    ClearKey,
+    Widevine,
+    FairPlay,
+    PlayReady,
+    /// Any other `KEYFORMAT`, including `identity`.
+    Other(String),
+}
+
+impl Key {
+    fn from_attributes<'a>(attributes: &Attributes<'a>) -> Result<Self, M3U8ParserError<&'a str>> {
+        let attribute = |key| attributes.get(key).map(|v| unquote(v).to_owned());
+
+        let method = match attribute("METHOD").as_deref() {
+            Some("NONE") => KeyMethod::None,
+            Some("AES-128") => KeyMethod::Aes128,
+            Some("SAMPLE-AES") => KeyMethod::SampleAes,
+            Some("SAMPLE-AES-CTR") => KeyMethod::SampleAesCtr,
+            method => {
+                return Err(M3U8ParserError::InvalidKeyMethod(
+                    method.unwrap_or("").to_owned(),
+                ))
+            }
+        };
+
+        Ok(Key {
+            method,
+            uri: attribute("URI"),
+            iv: attribute("IV"),
+            keyformat: attribute("KEYFORMAT"),
+            keyformatversions: attribute("KEYFORMATVERSIONS"),
+        })
+    }
+
+    /// Whether both keys are the same once `KEYFORMAT` and `KEYFORMATVERSIONS` default to
+    /// `identity` and `1`.
+    fn is_equivalent(&self, other: &Key) -> bool {
+        let keyformat = |k: &Key| k.keyformat.clone().unwrap_or_else(|| "identity".to_owned());
+        let versions = |k: &Key| {
+            k.keyformatversions
+                .clone()
+                .unwrap_or_else(|| "1".to_owned())
+        };
+
+        self.method == other.method
+            && self.uri == other.uri
+            && self.iv == other.iv
+            && keyformat(self) == keyformat(other)
+            && versions(self) == versions(other)
+    }
+
+    /// Whether the key is delivered as-is, i.e. `KEYFORMAT` is absent or `identity`.
+    pub fn is_identity(&self) -> bool {
+        self.keyformat.as_deref().unwrap_or("identity") == "identity"
+    }
+
+    /// Maps `KEYFORMAT` to a known key system, accepting both the DASH-IF system ID URNs and
+    /// the reverse-DNS names used by Apple and Microsoft.
+    pub fn key_system(&self) -> KeySystem {
+        let keyformat = self.keyformat.as_deref().unwrap_or("identity");
+
+        match keyformat.to_ascii_lowercase().as_str() {
+            "urn:uuid:e2719d58-a985-b3c9-781a-b030af78d30e" | "org.w3.clearkey" => {
+                KeySystem::ClearKey
+            }
+            "urn:uuid:edef8ba9-79d6-4ace-a3c8-27dcd51d21ed" | "com.widevine" => KeySystem::Widevine,
+            "urn:uuid:94ce86fb-07ff-4f43-adb8-93d2fa968ca2" | "com.apple.streamingkeydelivery" => {
+                KeySystem::FairPlay
+            }
+            "urn:uuid:9a04f079-9840-4286-ab92-e65be0885f95" | "com.microsoft.playready" => {
+                KeySystem::PlayReady
+            }
+            _ => KeySystem::Other(keyformat.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    /// Writes the key as an attribute list, re-quoting the quoted-string attributes.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "METHOD={}", self.method)?;
+
+        if let Some(ref uri) = self.uri {
+            write!(f, ",URI=\"{}\"", uri)?;
+        }
+
+        if let Some(ref iv) = self.iv {
+            write!(f, ",IV={}", iv)?;
+        }
+
+        if let Some(ref keyformat) = self.keyformat {
+            write!(f, ",KEYFORMAT=\"{}\"", keyformat)?;
+        }
+
+        if let Some(ref keyformatversions) = self.keyformatversions {
+            write!(f, ",KEYFORMATVERSIONS=\"{}\"", keyformatversions)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CueOut {
+    /// The planned length of the ad break in seconds, if the tag declares one.
+    pub duration: Option<f64>,
+}
+
+/// An ad break spanning the segments `start_segment..end_segment`.
+#[derive(Debug, PartialEq)]
+pub struct AdBreak {
+    pub start_segment: usize,
+    /// The index of the first segment after the break.
+    pub end_segment: usize,
+    pub duration: Option<f64>,
+}
+
+impl fmt::Display for MediaExtType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaExtType::Version => write!(f, "VERSION"),
+            MediaExtType::TargetDuration => write!(f, "TARGETDURATION"),
+            MediaExtType::MediaSequence => write!(f, "MEDIA-SEQUENCE"),
+            MediaExtType::DiscontinuitySequence => write!(f, "DISCONTINUITY-SEQUENCE"),
+            MediaExtType::PlaylistType => write!(f, "PLAYLIST-TYPE"),
+            MediaExtType::DateRange => write!(f, "DATERANGE"),
+            MediaExtType::Discontinuity => write!(f, "DISCONTINUITY"),
+            MediaExtType::Inf => write!(f, "INF"),
+            MediaExtType::ProgramDateTime => write!(f, "PROGRAM-DATE-TIME"),
+            MediaExtType::EndList => write!(f, "ENDLIST"),
+            MediaExtType::IndependentSegments => write!(f, "INDEPENDENT-SEGMENTS"),
+            MediaExtType::Start => write!(f, "START"),
+            MediaExtType::Album => write!(f, "ALB"),
+            MediaExtType::Artist => write!(f, "ART"),
+            MediaExtType::Genre => write!(f, "GENRE"),
+            MediaExtType::CueOut => write!(f, "CUE-OUT"),
+            MediaExtType::CueIn => write!(f, "CUE-IN"),
+            MediaExtType::Key => write!(f, "KEY"),
+            MediaExtType::Map => write!(f, "MAP"),
+            MediaExtType::Gap => write!(f, "GAP"),
+            MediaExtType::Bitrate => write!(f, "BITRATE"),
+            MediaExtType::ByteRange => write!(f, "BYTERANGE"),
+            MediaExtType::IFramesOnly => write!(f, "I-FRAMES-ONLY"),
+            MediaExtType::Part => write!(f, "PART"),
+            MediaExtType::PreloadHint => write!(f, "PRELOAD-HINT"),
+            MediaExtType::RenditionReport => write!(f, "RENDITION-REPORT"),
+            MediaExtType::Skip => write!(f, "SKIP"),
+            MediaExtType::ServerControl => write!(f, "SERVER-CONTROL"),
+            MediaExtType::Define => write!(f, "DEFINE"),
+            MediaExtType::Unknown(ext_type) | MediaExtType::UnknownExt(ext_type) => {
+                write!(f, "{}", ext_type)
+            }
+        }
+    }
+}
+
+impl MediaExtType {
+    /// The part of the tag between `#EXT` and its `Display` name, so that
+    /// `format!("#EXT{}{}", t.tag_prefix(), t)` reproduces the original tag.
+    pub fn tag_prefix(&self) -> &'static str {
+        match self {
+            MediaExtType::Inf
+            | MediaExtType::Album
+            | MediaExtType::Artist
+            | MediaExtType::Genre
+            | MediaExtType::UnknownExt(_) => "",
+            _ => "-X-",
+        }
+    }
+}
+
+impl<T: AsRef<str>> From<T> for MediaExtType {
+    fn from(s: T) -> Self {
+        let s = s.as_ref();
+
+        // `#EXTINF` and the extended M3U tags lack the `-X-` prefix, so a tag name is only
+        // recognised when it's paired with the right prefix, e.g. `#EXT-X-INF` and
+        // `#EXTVERSION` are both unknown.
+        match s.strip_prefix("-X-") {
+            Some("VERSION") => Self::Version,
+            Some("TARGETDURATION") => Self::TargetDuration,
+            Some("MEDIA-SEQUENCE") => Self::MediaSequence,
+            Some("DISCONTINUITY-SEQUENCE") => Self::DiscontinuitySequence,
+            Some("PLAYLIST-TYPE") => Self::PlaylistType,
+            Some("DATERANGE") => Self::DateRange,
+            Some("DISCONTINUITY") => Self::Discontinuity,
+            Some("PROGRAM-DATE-TIME") => Self::ProgramDateTime,
+            Some("ENDLIST") => Self::EndList,
+            Some("INDEPENDENT-SEGMENTS") => Self::IndependentSegments,
+            Some("START") => Self::Start,
+            Some("CUE-OUT") => Self::CueOut,
+            Some("CUE-IN") => Self::CueIn,
+            Some("KEY") => Self::Key,
+            Some("MAP") => Self::Map,
+            Some("GAP") => Self::Gap,
+            Some("BITRATE") => Self::Bitrate,
+            Some("BYTERANGE") => Self::ByteRange,
+            Some("I-FRAMES-ONLY") => Self::IFramesOnly,
+            Some("PART") => Self::Part,
+            Some("PRELOAD-HINT") => Self::PreloadHint,
+            Some("RENDITION-REPORT") => Self::RenditionReport,
+            Some("SKIP") => Self::Skip,
+            Some("SERVER-CONTROL") => Self::ServerControl,
+            Some("DEFINE") => Self::Define,
+            Some(name) => Self::Unknown(name.to_owned()),
+            None => match s {
+                "INF" => Self::Inf,
+                "ALB" => Self::Album,
+                "ART" => Self::Artist,
+                "GENRE" => Self::Genre,
+                _ => Self::UnknownExt(s.to_owned()),
+            },
+        }
+    }
+}
+
+pub fn read_media_list(data: &str) -> Result<MediaList<'_>, M3U8ParserError<&str>> {
+    read_media_list_mode(data, ParseMode::Lenient)
+}
+
+/// Like [`read_media_list`], but rejects input that does not follow the specification, such as
+/// single-quoted attribute values or an `#EXTINF` that isn't followed by a URI line.
+pub fn read_media_list_strict(data: &str) -> Result<MediaList<'_>, M3U8ParserError<&str>> {
+    read_media_list_mode(data, ParseMode::Strict)
+}
+
+/// Reads the whole of `reader` and parses it as a media playlist.
+pub fn read_media_list_from<R: Read>(
+    reader: &mut R,
+) -> Result<OwnedMediaList, M3U8ParserError<String>> {
+    let mut data = String::new();
+    reader.read_to_string(&mut data)?;
+
+    read_media_list(&data)
+        .map(MediaList::into_owned)
+        .map_err(M3U8ParserError::into_owned)
+}
+
+fn read_media_list_mode(
+    data: &str,
+    mode: ParseMode,
+) -> Result<MediaList<'_>, M3U8ParserError<&str>> {
+    let mut line = 1;
+
+    read_media_list_lines(data, mode, None, &mut |_, _, _| false, &mut line).map_err(|source| {
+        M3U8ParserError::AtLine {
+            line,
+            source: Box::new(source),
+        }
+    })
+}
+
+/// Parses the body of a tag this crate doesn't know about, such as a vendor's `#EXT-X-ACME-AD`.
+pub trait TagHandler {
+    type Output;
+
+    /// Parses everything after the tag's colon, which is empty if it has none. Returning `None`
+    /// keeps the tag in `ext_infos` as an unknown tag.
+    fn parse(&self, body: &str) -> Option<Self::Output>;
+}
+
+/// The [`TagHandler`]s used by [`read_media_list_with`], keyed by the whole tag name, e.g.
+/// `#EXT-X-ACME-AD`. The default registry has no handlers, so it parses like
+/// [`read_media_list`].
+pub struct TagRegistry<T> {
+    handlers: IndexMap<String, Box<dyn TagHandler<Output = T>>>,
+}
+
+impl<T> TagRegistry<T> {
+    pub fn new() -> Self {
+        TagRegistry {
+            handlers: IndexMap::new(),
+        }
+    }
+
+    /// Parses `tag` with `handler`, replacing any handler already registered for it. Tags this
+    /// crate knows about, like `#EXT-X-DATERANGE`, are never passed to a handler.
+    pub fn register(
+        mut self,
+        tag: impl Into<String>,
+        handler: impl TagHandler<Output = T> + 'static,
+    ) -> Self {
+        self.handlers.insert(tag.into(), Box::new(handler));
+        self
+    }
+}
+
+impl<T> Default for TagRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A tag parsed by a registered [`TagHandler`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomTag<T> {
+    pub tag: String,
+    /// The number of segments before the tag, as for [`MediaExtInfo::position`].
+    pub position: usize,
+    pub value: T,
+}
+
+/// Like [`read_media_list`], but parses the tags `registry` has a handler for into
+/// [`CustomTag`]s instead of keeping them in `ext_infos`.
+pub fn read_media_list_with<'a, T>(
+    data: &'a str,
+    registry: &TagRegistry<T>,
+) -> Result<(MediaList<'a>, Vec<CustomTag<T>>), M3U8ParserError<&'a str>> {
+    let mut custom_tags = Vec::new();
+    let mut line = 1;
+
+    let mut custom_tag = |tag: &str, body: &str, position| match registry
+        .handlers
+        .get(tag)
+        .and_then(|h| h.parse(body))
+    {
+        Some(value) => {
+            custom_tags.push(CustomTag {
+                tag: tag.to_owned(),
+                position,
+                value,
+            });
+
+            true
+        }
+        None => false,
+    };
+
+    let media_list =
+        read_media_list_lines(data, ParseMode::Lenient, None, &mut custom_tag, &mut line).map_err(
+            |source| M3U8ParserError::AtLine {
+                line,
+                source: Box::new(source),
+            },
+        )?;
+
+    Ok((media_list, custom_tags))
+}
+
+/// Like [`read_media_list`], but skips malformed lines instead of failing on the first one.
+/// Returns whatever parsed cleanly, along with an [`M3U8ParserError::AtLine`] for every line
+/// that was skipped. The media list is empty if the `#EXTM3U` header itself is invalid.
+pub fn read_media_list_lenient(data: &str) -> (MediaList<'_>, Vec<M3U8ParserError<String>>) {
+    let mut errors = Vec::new();
+    let mut line = 1;
+
+    let result = read_media_list_lines(
+        data,
+        ParseMode::Lenient,
+        Some(&mut errors),
+        &mut |_, _, _| false,
+        &mut line,
+    );
+
+    let mut errors = errors
+        .into_iter()
+        .map(M3U8ParserError::into_owned)
+        .collect::<Vec<_>>();
+
+    match result {
+        Ok(media_list) => (media_list, errors),
+        // Only an invalid header fails outright, and without one there's nothing to recover.
+        Err(source) => {
+            errors.push(M3U8ParserError::AtLine {
+                line,
+                source: Box::new(source.into_owned()),
+            });
+
+            (MediaListBuilder::new().build(), errors)
+        }
+    }
+}
+
+/// Parses the media list, keeping `line_number` at the line currently being read so that
+/// failures can be reported against it. If `errors` is given, a malformed line is added to it
+/// as an [`M3U8ParserError::AtLine`] and skipped instead of failing the parse. Unknown tags
+/// are offered to `custom_tag` along with their body and position first, and are only kept in
+/// `ext_infos` if it returns `false`.
+fn read_media_list_lines<'a>(
+    data: &'a str,
+    mode: ParseMode,
+    errors: Option<&mut Vec<M3U8ParserError<&'a str>>>,
+    custom_tag: &mut dyn FnMut(&str, &str, usize) -> bool,
+    line_number: &mut usize,
+) -> Result<MediaList<'a>, M3U8ParserError<&'a str>> {
+    let mut media_list = MediaListBuilder::new().media_list;
+
+    let (line_ending, header_attributes) =
+        read_media_events(data, mode, errors, line_number, &mut |event| match event {
+            MediaEvent::Version(version) => media_list.version = version,
+            MediaEvent::PlaylistType(playlist_type) => {
+                media_list.playlist_type = Some(playlist_type)
+            }
+            MediaEvent::TargetDuration(target_duration) => {
+                media_list.target_duration = target_duration
+            }
+            MediaEvent::MediaSequence(media_sequence) => media_list.media_sequence = media_sequence,
+            MediaEvent::DiscontinuitySequence(discontinuity_sequence) => {
+                media_list.discontinuity_sequence = discontinuity_sequence
+            }
+            MediaEvent::IndependentSegments => media_list.independent_segments = true,
+            MediaEvent::IFramesOnly => media_list.iframes_only = true,
+            MediaEvent::Start(start) => media_list.start = Some(start),
+            MediaEvent::ServerControl(server_control) => {
+                media_list.server_control = Some(server_control)
+            }
+            MediaEvent::Define { name, value } => {
+                media_list.defines.insert(name, value);
+            }
+            // Kept alongside `MediaSegment::discontinuity` for backward compatibility.
+            MediaEvent::Discontinuity => media_list.ext_infos.push(MediaExtInfo {
+                ext_type: MediaExtType::Discontinuity,
+                attributes: IndexMap::new(),
+                position: media_list.media_segments.len(),
+            }),
+            MediaEvent::Segment(segment) => media_list.media_segments.push(segment),
+            MediaEvent::PreloadHint(preload_hint) => media_list.preload_hints.push(preload_hint),
+            MediaEvent::RenditionReport(rendition_report) => {
+                media_list.rendition_reports.push(rendition_report)
+            }
+            MediaEvent::Skip(skip) => media_list.skip = Some(skip),
+            MediaEvent::ExtInfo(ext_info) => {
+                let handled = match ext_info.ext_type {
+                    MediaExtType::Unknown(_) | MediaExtType::UnknownExt(_) => custom_tag(
+                        &format!(
+                            "#EXT{}{}",
+                            ext_info.ext_type.tag_prefix(),
+                            ext_info.ext_type
+                        ),
+                        ext_info
+                            .attributes
+                            .get("UNKNOWN")
+                            .map_or("", |b| b.as_ref()),
+                        ext_info.position,
+                    ),
+                    _ => false,
+                };
+
+                if !handled {
+                    media_list.ext_infos.push(ext_info);
+                }
+            }
+            MediaEvent::EndList => media_list.end_list = true,
+        })?;
+
+    media_list.line_ending = line_ending;
+    media_list.header_attributes = header_attributes;
+
+    Ok(media_list)
+}
+
+/// A tag or segment of a media playlist, as passed to [`parse_media_events`] in the order it
+/// appears in the input.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum MediaEvent<'a> {
+    Version(u8),
+    PlaylistType(PlaylistType),
+    TargetDuration(u8),
+    MediaSequence(u32),
+    DiscontinuitySequence(u32),
+    IndependentSegments,
+    IFramesOnly,
+    Start(Start),
+    ServerControl(ServerControl),
+    /// An `#EXT-X-DEFINE` giving a variable a `VALUE`. Other definitions are an `ExtInfo`.
+    Define {
+        name: String,
+        value: String,
+    },
+    /// An `#EXT-X-DISCONTINUITY`. The segment after it also has `discontinuity` set.
+    Discontinuity,
+    /// A segment, along with every tag that applies to it.
+    Segment(MediaSegment),
+    PreloadHint(PreloadHint),
+    RenditionReport(RenditionReport),
+    Skip(Skip),
+    /// Any other tag, such as `#EXT-X-DATERANGE`, as it would be kept in `ext_infos`.
+    ExtInfo(MediaExtInfo<'a>),
+    EndList,
+}
+
+/// Walks a media playlist, passing each tag and segment to `f` as it's read instead of
+/// collecting them into a [`MediaList`]. This keeps memory use flat for very long playlists.
+/// Parses like [`read_media_list`], so `f` may have been called for the lines before an error.
+pub fn parse_media_events<'a, F: FnMut(MediaEvent<'a>)>(
+    data: &'a str,
+    mut f: F,
+) -> Result<(), M3U8ParserError<&'a str>> {
+    let mut line = 1;
+
+    read_media_events(data, ParseMode::Lenient, None, &mut line, &mut f)
+        .map(|_| ())
+        .map_err(|source| M3U8ParserError::AtLine {
+            line,
+            source: Box::new(source),
+        })
+}
+
+/// Passes each tag and segment to `on_event` as it's read, returning the line ending and
+/// attributes of the `#EXTM3U` header. `line_number` and `errors` are as for
+/// [`read_media_list_lines`].
+fn read_media_events<'a>(
+    data: &'a str,
+    mode: ParseMode,
+    mut errors: Option<&mut Vec<M3U8ParserError<&'a str>>>,
+    line_number: &mut usize,
+    on_event: &mut dyn FnMut(MediaEvent<'a>),
+) -> Result<(LineEnding, Attributes<'a>), M3U8ParserError<&'a str>> {
+    let (i, identifier) = ext_identifier(strip_bom(data), mode).finish()?;
+
+    let line_ending = if identifier.ends_with("\r\n") {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    };
+
+    let header_attributes = header_attributes(identifier["#EXTM3U".len()..].trim());
+
+    // The header is line 1, and a tag and the URI following it are reported by the tag's line.
+    let mut remaining_lines = i.lines().zip(2..).filter(|(line, _)| !is_comment(line));
+
+    // Tags are positioned by the number of segments before them.
+    let mut segment_count = 0;
+    let mut has_target_duration = false;
+
+    let mut pending = PendingSegment::default();
+    let mut current_key = None;
+    let mut current_map = None;
+    let mut current_bitrate = None;
+
+    while let Some((line, number)) = remaining_lines.next() {
+        *line_number = number;
+
+        // A closure, so a malformed line can be recorded and skipped when `errors` is given.
+        let mut read_line = || -> Result<(), M3U8ParserError<&'a str>> {
+            let (i, ext_type) = ext_type::<MediaExtType>(line).finish()?;
+
+            match ext_type {
+                MediaExtType::DateRange => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    on_event(MediaEvent::ExtInfo(MediaExtInfo {
+                        ext_type,
+                        attributes,
+                        position: segment_count,
+                    }))
+                }
+                MediaExtType::Unknown(_) | MediaExtType::UnknownExt(_) => {
+                    let (_, unknown_str) = to_end(i).finish()?;
+
+                    let mut attributes = IndexMap::new();
+
+                    // Tag names never contain ':', so any colon separates the name from a value.
+                    // Tags without one, like `#EXTFOO`, get no value so `save` omits the colon.
+                    if line.contains(':') {
+                        attributes.insert("UNKNOWN".into(), unknown_str.into());
+                    }
+
+                    on_event(MediaEvent::ExtInfo(MediaExtInfo {
+                        ext_type,
+                        attributes,
+                        position: segment_count,
+                    }))
+                }
+                MediaExtType::ProgramDateTime => {
+                    let (_, program_date_time) = not_newline(i).finish()?;
+
+                    pending.program_date_time = Some(program_date_time.to_owned());
+                }
+                MediaExtType::Album => {
+                    let (_, album) = not_newline(i).finish()?;
+
+                    pending.album = Some(album.to_owned());
+                }
+                MediaExtType::Artist => {
+                    let (_, artist) = not_newline(i).finish()?;
+
+                    pending.artist = Some(artist.to_owned());
+                }
+                MediaExtType::Genre => {
+                    let (_, genre) = not_newline(i).finish()?;
+
+                    pending.genre = Some(genre.to_owned());
+                }
+                MediaExtType::Inf => {
+                    // Taken, along with the lines up to the URI, before anything can fail, so a
+                    // malformed segment is skipped whole rather than leaking into the next one.
+                    let mut pending = std::mem::take(&mut pending);
+                    let mut uri_line = remaining_lines.next();
+                    let mut byte_range = None;
+
+                    // `#EXT-X-BYTERANGE` is usually written between `#EXTINF` and the URI.
+                    while let Some(b) =
+                        uri_line.and_then(|(l, _)| l.strip_prefix("#EXT-X-BYTERANGE:"))
+                    {
+                        byte_range = Some(b);
+                        uri_line = remaining_lines.next();
+                    }
+
+                    let (_, (duration, tit)) = inf_duration_title(i, mode).finish()?;
+
+                    // Some encoders pad the comma with spaces, e.g. `#EXTINF:6.000 , live`.
+                    let tit = match mode {
+                        ParseMode::Lenient => tit.trim_start(),
+                        ParseMode::Strict => tit,
+                    };
+
+                    if let Some(byte_range) = byte_range {
+                        pending.byte_range = Some(ByteRange::parse(byte_range.trim())?);
+                    }
+
+                    let has_uri = matches!(uri_line, Some((l, _)) if !l.starts_with('#'));
+
+                    // Lenient parsing drops a trailing `#EXTINF` without a URI.
+                    if mode == ParseMode::Strict && !has_uri {
+                        return Err(M3U8ParserError::MissingSegmentUri);
+                    }
+
+                    if let Some((stream_inf_location, _)) = uri_line {
+                        let duration = duration.trim().parse::<f64>()?;
+                        let mut title = None;
+
+                        if !tit.is_empty() {
+                            title = Some(tit.to_owned());
+                        }
+
+                        let uri = unquote(stream_inf_location).to_owned();
+
+                        segment_count += 1;
+
+                        on_event(MediaEvent::Segment(MediaSegment {
+                            duration,
+                            title,
+                            uri,
+                            program_date_time: pending.program_date_time,
+                            discontinuity: pending.discontinuity,
+                            album: pending.album,
+                            artist: pending.artist,
+                            genre: pending.genre,
+                            cue_out: pending.cue_out,
+                            cue_in: pending.cue_in,
+                            key: current_key.clone(),
+                            map: current_map.clone(),
+                            gap: pending.gap,
+                            bitrate: current_bitrate,
+                            byte_range: pending.byte_range,
+                            parts: pending.parts,
+                        }))
+                    }
+                }
+                MediaExtType::Version => {
+                    let (_, ver) = not_newline(i).finish()?;
+                    on_event(MediaEvent::Version(ver.parse::<u8>()?));
+                }
+                MediaExtType::PlaylistType => {
+                    let (_, pl_type) = not_newline(i).finish()?;
+                    on_event(MediaEvent::PlaylistType(match pl_type {
+                        "VOD" => PlaylistType::Vod,
+                        "EVENT" => PlaylistType::Event,
+                        _ => return Err(M3U8ParserError::InvalidPlaylistType(pl_type.to_owned())),
+                    }));
+                }
+                MediaExtType::TargetDuration => {
+                    let (_, dur) = not_newline(i).finish()?;
+                    on_event(MediaEvent::TargetDuration(dur.parse::<u8>()?));
+
+                    has_target_duration = true;
+                }
+                MediaExtType::MediaSequence => {
+                    let (_, media_seq) = not_newline(i).finish()?;
+                    on_event(MediaEvent::MediaSequence(media_seq.trim().parse::<u32>()?));
+                }
+                MediaExtType::DiscontinuitySequence => {
+                    let (_, discontinuity_seq) = not_newline(i).finish()?;
+                    on_event(MediaEvent::DiscontinuitySequence(
+                        discontinuity_seq.trim().parse::<u32>()?,
+                    ));
+                }
+                MediaExtType::EndList => {
+                    on_event(MediaEvent::EndList);
+                }
+                MediaExtType::IndependentSegments => {
+                    on_event(MediaEvent::IndependentSegments);
+                }
+                MediaExtType::IFramesOnly => {
+                    on_event(MediaEvent::IFramesOnly);
+                }
+                MediaExtType::ByteRange => {
+                    let (_, byte_range) = not_newline(i).finish()?;
+
+                    pending.byte_range = Some(ByteRange::parse(byte_range.trim())?);
+                }
+                MediaExtType::Start => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    on_event(MediaEvent::Start(Start::from_attributes(&attributes)?));
+                }
+                MediaExtType::CueOut => {
+                    // Both `#EXT-X-CUE-OUT:30` and `#EXT-X-CUE-OUT:DURATION=30` are in use.
+                    let duration = if i.contains('=') {
+                        let (_, attributes) = attributes(i, mode).finish()?;
+
+                        attributes.get("DURATION").map(|d| unquote(d).to_owned())
+                    } else {
+                        Some(i.to_owned()).filter(|d| !d.is_empty())
+                    };
+
+                    pending.cue_out = Some(CueOut {
+                        duration: duration.map(|d| d.parse::<f64>()).transpose()?,
+                    });
+                }
+                MediaExtType::CueIn => {
+                    pending.cue_in = true;
+                }
+                MediaExtType::Gap => {
+                    pending.gap = true;
+                }
+                MediaExtType::Bitrate => {
+                    let (_, bitrate) = not_newline(i).finish()?;
+
+                    // A bitrate applies to every following segment until the next one replaces it.
+                    current_bitrate = Some(bitrate.trim().parse::<u32>()?);
+                }
+                MediaExtType::Part => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    pending.parts.push(Part::from_attributes(&attributes)?);
+                }
+                MediaExtType::PreloadHint => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    on_event(MediaEvent::PreloadHint(PreloadHint::from_attributes(
+                        &attributes,
+                    )?));
+                }
+                MediaExtType::RenditionReport => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    on_event(MediaEvent::RenditionReport(
+                        RenditionReport::from_attributes(&attributes)?,
+                    ));
+                }
+                MediaExtType::Skip => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    on_event(MediaEvent::Skip(Skip::from_attributes(&attributes)?));
+                }
+                MediaExtType::ServerControl => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    on_event(MediaEvent::ServerControl(ServerControl::from_attributes(
+                        &attributes,
+                    )?));
+                }
+                MediaExtType::Define => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    match define(&attributes) {
+                        Some((name, value)) => on_event(MediaEvent::Define { name, value }),
+                        None => on_event(MediaEvent::ExtInfo(MediaExtInfo {
+                            ext_type,
+                            attributes,
+                            position: segment_count,
+                        })),
+                    }
+                }
+                MediaExtType::Key => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    let key = Key::from_attributes(&attributes)?;
+
+                    // A key applies to every following segment until the next one replaces it.
+                    current_key = Some(key).filter(|k| k.method != KeyMethod::None);
+                }
+                MediaExtType::Map => {
+                    let (_, attributes) = attributes(i, mode).finish()?;
+
+                    let byte_range = attributes
+                        .get("BYTERANGE")
+                        .map(|b| ByteRange::parse(unquote(b)))
+                        .transpose()?;
+
+                    current_map = Some(MapInfo {
+                        uri: attributes.get("URI").map_or("", |u| unquote(u)).to_owned(),
+                        byte_range,
+                    });
+                }
+                MediaExtType::Discontinuity => {
+                    pending.discontinuity = true;
+
+                    on_event(MediaEvent::Discontinuity);
+                }
+            }
+
+            Ok(())
+        };
+
+        if let Err(source) = read_line() {
+            match errors {
+                Some(ref mut errors) => errors.push(M3U8ParserError::AtLine {
+                    line: number,
+                    source: Box::new(source),
+                }),
+                None => return Err(source),
+            }
+        }
+    }
+
+    // A missing tag isn't on any line, so it's reported against the `#EXTM3U` header.
+    if mode == ParseMode::Strict && !has_target_duration {
+        *line_number = 1;
+
+        return Err(M3U8ParserError::MissingRequiredTag("TARGETDURATION"));
+    }
+
+    Ok((line_ending, header_attributes))
+}
+
+/// The tags read since the last segment that only apply to the next one.
+#[derive(Default)]
+struct PendingSegment {
+    program_date_time: Option<String>,
+    discontinuity: bool,
+    album: Option<String>,
+    artist: Option<String>,
+    genre: Option<String>,
+    cue_out: Option<CueOut>,
+    cue_in: bool,
+    gap: bool,
+    byte_range: Option<ByteRange>,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MediaListBuildOptions {
+    pub version: u8,
+    pub media_sequence: u32,
+    /// Marks the playlist as complete with `#EXT-X-ENDLIST`.
+    pub end_list: bool,
+}
+
+/// Builds a media list from `(uri, duration)` pairs, computing the target duration from the
+/// longest segment.
+pub fn build_media_list(
+    segments: &[(String, f64)],
+    options: MediaListBuildOptions,
+) -> MediaList<'static> {
+    let media_segments = segments
+        .iter()
+        .map(|(uri, duration)| MediaSegment {
+            // Kept as given, since a `Duration` can't hold a negative value.
+            duration: *duration,
+            ..MediaSegment::new(Duration::ZERO, uri.clone())
+        })
+        .collect::<Vec<_>>();
+
+    let mut builder = MediaListBuilder::new()
+        .version(options.version)
+        .media_sequence(options.media_sequence);
+
+    for segment in media_segments {
+        builder = builder.push_segment(segment);
+    }
+
+    if options.end_list {
+        builder = builder.end_list();
+    }
+
+    builder.build()
+}
+
+/// Builds a [`MediaList`] from scratch. Anything left unset takes the value
+/// [`read_media_list`] gives a missing tag.
+#[derive(Debug, Clone)]
+pub struct MediaListBuilder {
+    media_list: MediaList<'static>,
+    target_duration: Option<u8>,
+}
+
+impl Default for MediaListBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaListBuilder {
+    pub fn new() -> Self {
+        MediaListBuilder {
+            media_list: MediaList {
+                version: 0,
+                playlist_type: None,
+                target_duration: 0,
+                media_sequence: 0,
+                discontinuity_sequence: 0,
+                media_segments: Vec::new(),
+                ext_infos: Vec::new(),
+                end_list: false,
+                independent_segments: false,
+                iframes_only: false,
+                start: None,
+                server_control: None,
+                defines: IndexMap::new(),
+                preload_hints: Vec::new(),
+                rendition_reports: Vec::new(),
+                skip: None,
+                line_ending: LineEnding::default(),
+                header_attributes: Attributes::new(),
+            },
+            target_duration: None,
+        }
+    }
+
+    pub fn version(mut self, version: u8) -> Self {
+        self.media_list.version = version;
+        self
+    }
+
+    pub fn playlist_type(mut self, playlist_type: PlaylistType) -> Self {
+        self.media_list.playlist_type = Some(playlist_type);
+        self
+    }
+
+    /// Overrides the target duration, which is otherwise computed from the longest segment.
+    pub fn target_duration(mut self, target_duration: u8) -> Self {
+        self.target_duration = Some(target_duration);
+        self
+    }
+
+    pub fn media_sequence(mut self, media_sequence: u32) -> Self {
+        self.media_list.media_sequence = media_sequence;
+        self
+    }
+
+    pub fn push_segment(mut self, segment: MediaSegment) -> Self {
+        self.media_list.media_segments.push(segment);
+        self
+    }
+
+    /// Marks the playlist as complete with `#EXT-X-ENDLIST`.
+    pub fn end_list(mut self) -> Self {
+        self.media_list.end_list = true;
+        self
+    }
+
+    pub fn build(self) -> MediaList<'static> {
+        let mut media_list = self.media_list;
+
+        match self.target_duration {
+            Some(target_duration) => media_list.target_duration = target_duration,
+            None => media_list.recompute_target_duration(),
+        }
+
+        media_list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_read_playlist() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let first_ext_info = playlist.ext_infos.first().unwrap();
+
+        assert_eq!(
+            first_ext_info.ext_type,
+            PlaylistExtType::Unknown("TWITCH-INFO".to_owned())
+        );
+        assert_eq!(
+            first_ext_info.attributes.get("MANIFEST-NODE-TYPE"),
+            Some(&"\"weaver_cluster\"".into())
+        );
+        assert_eq!(
+            first_ext_info.attributes.get("BROADCAST-ID"),
+            Some(&"\"40032678348\"".into())
+        );
+        assert_eq!(
+            first_ext_info.attributes.get("USER-COUNTRY"),
+            Some(&"\"GB\"".into())
+        );
+
+        let media_1080p = playlist.ext_infos.get(1).unwrap();
+        assert_eq!(media_1080p.ext_type, PlaylistExtType::Media);
+        assert_eq!(media_1080p.attributes.get("TYPE"), Some(&"VIDEO".into()));
+        assert_eq!(
+            media_1080p.attributes.get("NAME"),
+            Some(&"\"1080p60 (source)\"".into())
+        );
+        assert_eq!(
+            media_1080p.attributes.get("GROUP-ID"),
+            Some(&"\"chunked\"".into())
+        );
+
+        let stream_inf_1080p = playlist.ext_infos.get(2).unwrap();
+
+        assert_eq!(stream_inf_1080p.ext_type, PlaylistExtType::StreamInf);
+        assert_eq!(
+            stream_inf_1080p.attributes.get("RESOLUTION"),
+            Some(&"1920x1080".into())
+        );
+        assert_eq!(
+            stream_inf_1080p.attributes.get("VIDEO"),
+            Some(&"\"chunked\"".into())
+        );
+    }
+
+    #[test]
+    fn test_read_playlist_session_data() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Example\",LANGUAGE=\"en\"\n",
+            "#EXT-X-SESSION-DATA:DATA-ID=\"com.example.lyrics\",URI=\"lyrics.json\"\n",
+        );
+
+        let playlist = read_playlist(data).unwrap();
+
+        let inline = playlist.ext_infos.first().unwrap();
+        assert_eq!(inline.ext_type, PlaylistExtType::SessionData);
+        assert_eq!(inline.session_data_value(), Some("Example"));
+        assert_eq!(inline.session_data_uri(), None);
+        assert_eq!(inline.session_data_language(), Some("en"));
+
+        let external = playlist.ext_infos.get(1).unwrap();
+        assert_eq!(external.session_data_value(), None);
+        assert_eq!(external.session_data_uri(), Some("lyrics.json"));
+        assert_eq!(external.session_data_language(), None);
+
+        assert_eq!(
+            playlist.session_data(),
+            vec![
+                SessionData {
+                    data_id: "com.example.title".to_owned(),
+                    value: Some("Example".to_owned()),
+                    uri: None,
+                    language: Some("en".to_owned()),
+                },
+                SessionData {
+                    data_id: "com.example.lyrics".to_owned(),
+                    value: None,
+                    uri: Some("lyrics.json".to_owned()),
+                    language: None,
+                },
+            ]
+        );
+
+        assert_eq!(
+            read_playlist("#EXTM3U\n#EXT-X-SESSION-DATA:DATA-ID=\"a\",VALUE=\"b\",URI=\"c\"\n")
+                .unwrap_err(),
+            M3U8ParserError::AtLine {
+                line: 2,
+                source: Box::new(M3U8ParserError::InvalidSessionData("a".to_owned())),
+            }
+        );
+        assert_eq!(
+            read_playlist("#EXTM3U\n#EXT-X-SESSION-DATA:DATA-ID=\"a\"\n").unwrap_err(),
+            M3U8ParserError::AtLine {
+                line: 2,
+                source: Box::new(M3U8ParserError::InvalidSessionData("a".to_owned())),
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_playlist_session_key() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-SESSION-KEY:METHOD=AES-128,URI=\"https://example.com/key\",",
+            "IV=0x9c7db8778570d05c3177c349fd9236aa\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+        );
+
+        let playlist = read_playlist(data).unwrap();
+
+        assert_eq!(
+            playlist.session_keys(),
+            vec![Key {
+                method: KeyMethod::Aes128,
+                uri: Some("https://example.com/key".to_owned()),
+                iv: Some("0x9c7db8778570d05c3177c349fd9236aa".to_owned()),
+                keyformat: None,
+                keyformatversions: None,
+            }]
+        );
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+
+        assert_eq!(
+            read_playlist("#EXTM3U\n#EXT-X-SESSION-KEY:METHOD=NONE\n").unwrap_err(),
+            M3U8ParserError::AtLine {
+                line: 2,
+                source: Box::new(M3U8ParserError::InvalidSessionKey),
+            }
+        );
+    }
+
+    #[test]
+    fn test_attr() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let media = &playlist.ext_infos[1];
+        let stream_inf = &playlist.ext_infos[2];
+
+        assert_eq!(media.attr("GROUP-ID").as_deref(), Some("chunked"));
+        assert_eq!(
+            media.attributes.get("GROUP-ID"),
+            Some(&"\"chunked\"".into())
+        );
+        assert_eq!(media.attr("AUTOSELECT").as_deref(), Some("YES"));
+        assert_eq!(stream_inf.attr("RESOLUTION").as_deref(), Some("1920x1080"));
+        assert_eq!(stream_inf.attr("MISSING"), None);
+
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let date_range = &media_list.ext_infos[2];
+
+        assert_eq!(
+            date_range.attr("CLASS").as_deref(),
+            Some("twitch-stitched-ad")
+        );
+        assert_eq!(date_range.attr("DURATION").as_deref(), Some("15.050"));
+    }
+
+    #[test]
+    fn test_resolution() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let resolutions = playlist
+            .ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::StreamInf)
+            .map(|e| e.resolution().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            resolutions[3],
+            Some(Resolution {
+                width: 852,
+                height: 480,
+            })
+        );
+        assert_eq!(resolutions.last(), Some(&None));
+        assert_eq!(
+            resolutions.iter().flatten().max().unwrap().to_string(),
+            "1920x1080"
+        );
+
+        assert_eq!(
+            Resolution::parse("1920X"),
+            Err(M3U8ParserError::InvalidResolution("1920X".to_owned()))
+        );
+        assert!(Resolution::parse("1920x").is_err());
+        assert!(Resolution::parse("x1080").is_err());
+    }
+
+    #[test]
+    fn test_variants() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let variants = playlist.variants();
+
+        assert_eq!(variants.len(), 7);
+        assert_eq!(
+            variants[0],
+            VariantStream {
+                uri: playlist.first_playlist_link().unwrap().to_owned(),
+                bandwidth: 6_893_855,
+                average_bandwidth: None,
+                resolution: Some(Resolution {
+                    width: 1920,
+                    height: 1080,
+                }),
+                codecs: vec!["avc1.64002A".to_owned(), "mp4a.40.2".to_owned()],
+                frame_rate: None,
+                audio: None,
+                video: Some("chunked".to_owned()),
+                subtitles: None,
+                closed_captions: None,
+            }
+        );
+        assert_eq!(variants[6].resolution, None);
+
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,AVERAGE-BANDWIDTH=2500000,FRAME-RATE=29.970,AUDIO=\"aac\",SUBTITLES=\"subs\",CLOSED-CAPTIONS=\"cc\"\n",
+            "720p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=1000000,CLOSED-CAPTIONS=NONE\n",
+            "480p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:RESOLUTION=640x360\n",
+            "360p/index.m3u8\n",
+        ))
+        .unwrap();
+        let variants = playlist.variants();
+
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].average_bandwidth, Some(2_500_000));
+        assert_eq!(variants[0].frame_rate, Some(29.97));
+        assert_eq!(variants[0].audio.as_deref(), Some("aac"));
+        assert_eq!(variants[0].subtitles.as_deref(), Some("subs"));
+        assert_eq!(variants[0].closed_captions.as_deref(), Some("cc"));
+        assert_eq!(variants[1].closed_captions, None);
+        assert_eq!(playlist.ext_infos.len(), 3);
+    }
+
+    #[test]
+    fn test_best_variant_link() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=800000\n",
+            "480p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=5000000\n",
+            "1080p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=5000000\n",
+            "1080p-backup/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=2500000\n",
+            "720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(playlist.first_playlist_link(), Some("480p/index.m3u8"));
+        assert_eq!(playlist.best_variant_link(), Some("1080p/index.m3u8"));
+
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let playlist = read_playlist(&test_file).unwrap();
+
+        assert_eq!(playlist.best_variant_link(), playlist.first_playlist_link());
+        assert_eq!(
+            read_playlist("#EXTM3U\n").unwrap().best_variant_link(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_variant_for_height() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080\n",
+            "1080p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=64000\n",
+            "audio/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=854x480\n",
+            "480p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=2500000,RESOLUTION=1280x720\n",
+            "720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(playlist.variant_for_height(720), Some("720p/index.m3u8"));
+        assert_eq!(playlist.variant_for_height(900), Some("720p/index.m3u8"));
+        assert_eq!(playlist.variant_for_height(2160), Some("1080p/index.m3u8"));
+        assert_eq!(playlist.variant_for_height(240), Some("480p/index.m3u8"));
+        assert_eq!(
+            read_playlist("#EXTM3U\n").unwrap().variant_for_height(720),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bandwidth() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let stream_infs = playlist
+            .ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::StreamInf)
+            .collect::<Vec<_>>();
+
+        assert_eq!(stream_infs[0].bandwidth(), Ok(Some(6_893_855)));
+        assert_eq!(stream_infs[0].average_bandwidth(), Ok(None));
+
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,AVERAGE-BANDWIDTH=2500000\n",
+            "720p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=fast\n",
+            "480p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            playlist.ext_infos[0].average_bandwidth(),
+            Ok(Some(2_500_000))
+        );
+        assert!(matches!(
+            playlist.ext_infos[1].bandwidth(),
+            Err(M3U8ParserError::ParseIntError(_))
+        ));
+    }
+
+    #[test]
+    fn test_frame_rate() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=6000000,FRAME-RATE=60.000\n",
+            "1080p60/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=1000000,FRAME-RATE=fast\n",
+            "480p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(playlist.ext_infos[0].frame_rate(), Ok(Some(60.0)));
+        assert_eq!(playlist.ext_infos[1].frame_rate(), Ok(None));
+        assert!(matches!(
+            playlist.ext_infos[2].frame_rate(),
+            Err(M3U8ParserError::ParseFloatError(_))
+        ));
+    }
+
+    #[test]
+    fn test_hdcp_level_and_video_range() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=20000000,VIDEO-RANGE=PQ,HDCP-LEVEL=TYPE-1\n",
+            "2160p-hdr/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=6000000,VIDEO-RANGE=SDR,HDCP-LEVEL=NONE\n",
+            "1080p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,VIDEO-RANGE=DV,HDCP-LEVEL=TYPE-2\n",
+            "720p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=1000000\n",
+            "480p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(playlist.ext_infos[0].hdcp_level(), Some(HdcpLevel::Type1));
+        assert_eq!(playlist.ext_infos[0].video_range(), Some(VideoRange::Pq));
+        assert_eq!(playlist.ext_infos[1].hdcp_level(), Some(HdcpLevel::None));
+        assert_eq!(playlist.ext_infos[1].video_range(), Some(VideoRange::Sdr));
+        assert_eq!(
+            playlist.ext_infos[2].hdcp_level(),
+            Some(HdcpLevel::Unknown("TYPE-2".to_owned()))
+        );
+        assert_eq!(
+            playlist.ext_infos[2].video_range(),
+            Some(VideoRange::Unknown("DV".to_owned()))
+        );
+        assert_eq!(playlist.ext_infos[3].hdcp_level(), None);
+        assert_eq!(playlist.ext_infos[3].video_range(), None);
+    }
+
+    #[test]
+    fn test_codecs() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,CODECS=\"avc1.4D401F,mp4a.40.2\"\n",
+            "720p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\n",
+            "audio/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=1000000\n",
+            "480p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            playlist.ext_infos[0].codecs(),
+            vec!["avc1.4D401F", "mp4a.40.2"]
+        );
+        assert_eq!(playlist.ext_infos[1].codecs(), vec!["mp4a.40.2"]);
+        assert!(playlist.ext_infos[1]
+            .codecs()
+            .iter()
+            .all(|c| is_audio_codec(c)));
+        assert!(playlist.ext_infos[2].codecs().is_empty());
+    }
+
+    #[test]
+    fn test_clone() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let mut cloned = media_list.clone();
+
+        assert_eq!(cloned, media_list);
+
+        cloned.media_sequence += 1;
+
+        assert_eq!(media_list.media_sequence, 4508);
+        assert_ne!(cloned, media_list);
+
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let mut cloned = playlist.clone();
+        cloned.ext_infos.pop();
+
+        assert_ne!(cloned, playlist);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let playlist: Playlist = test_file.parse().unwrap();
+
+        assert_eq!(playlist, read_playlist(&test_file).unwrap());
+
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+        let media_list: MediaList = test_file.parse().unwrap();
+
+        assert_eq!(media_list, read_media_list(&test_file).unwrap());
+
+        assert!(matches!(
+            "#EXTM3U\n#EXTINF:abc,\nsegment.ts\n".parse::<MediaList>(),
+            Err(M3U8ParserError::AtLine { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_try_from() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        assert_eq!(
+            Playlist::try_from(test_file.as_str()).unwrap(),
+            read_playlist(&test_file).unwrap()
+        );
+
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert_eq!(
+            MediaList::try_from(test_file.as_str()).unwrap(),
+            read_media_list(&test_file).unwrap()
+        );
+
+        assert!(matches!(
+            Playlist::try_from("not a playlist\n"),
+            Err(M3U8ParserError::AtLine { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let playlist = read_playlist(&test_file).unwrap().into_owned();
+
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+        let media_list = read_media_list(&test_file).unwrap().into_owned();
+
+        drop(test_file);
+
+        let (playlist, media_list) = std::thread::spawn(move || (playlist, media_list))
+            .join()
+            .unwrap();
+
+        assert_eq!(
+            playlist.ext_infos[1].attributes.get("GROUP-ID"),
+            Some(&"\"chunked\"".into())
+        );
+        assert_eq!(
+            media_list.ext_infos[2].attr("CLASS").as_deref(),
+            Some("twitch-stitched-ad")
+        );
+        assert_eq!(media_list.media_segments.len(), 15);
+    }
+
+    #[test]
+    fn test_parse() {
+        let playlist_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let media_list_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert_eq!(
+            parse(&playlist_file),
+            Ok(PlaylistKind::Master(read_playlist(&playlist_file).unwrap()))
+        );
+        assert_eq!(
+            parse(&media_list_file),
+            Ok(PlaylistKind::Media(
+                read_media_list(&media_list_file).unwrap()
+            ))
+        );
+
+        let ambiguous = format!("{}{}", playlist_file, &media_list_file["#EXTM3U\n".len()..]);
+
+        assert_eq!(
+            parse(&ambiguous),
+            Err(M3U8ParserError::AmbiguousPlaylistKind)
+        );
+        assert_eq!(
+            parse("#EXTM3U\n"),
+            Err(M3U8ParserError::AmbiguousPlaylistKind)
+        );
+    }
+
+    #[test]
+    fn test_renditions() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",LANGUAGE=\"en\",DEFAULT=YES,AUTOSELECT=YES,URI=\"audio/en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Deutsch\",LANGUAGE=\"de\",DEFAULT=NO,AUTOSELECT=YES,URI=\"audio/de.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"English\",LANGUAGE=\"en\",URI=\"subs/en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=CLOSED-CAPTIONS,GROUP-ID=\"cc\",NAME=\"English\",INSTREAM-ID=\"CC1\"\n",
+            "#EXT-X-MEDIA:TYPE=VIDEO,GROUP-ID=\"cam\",NAME=\"Wide\",DEFAULT=YES\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,AUDIO=\"aac\",SUBTITLES=\"subs\",CLOSED-CAPTIONS=\"cc\",VIDEO=\"cam\"\n",
+            "720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            playlist.audio_renditions(),
+            vec![
+                Rendition {
+                    group_id: "aac".to_owned(),
+                    name: "English".to_owned(),
+                    language: Some("en".to_owned()),
+                    default: true,
+                    autoselect: true,
+                    uri: Some("audio/en.m3u8".to_owned()),
+                    channel_spec: None,
+                },
+                Rendition {
+                    group_id: "aac".to_owned(),
+                    name: "Deutsch".to_owned(),
+                    language: Some("de".to_owned()),
+                    default: false,
+                    autoselect: true,
+                    uri: Some("audio/de.m3u8".to_owned()),
+                    channel_spec: None,
+                },
+            ]
+        );
+
+        let subtitles = playlist.subtitle_renditions();
+
+        assert_eq!(subtitles.len(), 1);
+        assert_eq!(subtitles[0].uri.as_deref(), Some("subs/en.m3u8"));
+        assert!(!subtitles[0].default);
+
+        let closed_captions = playlist.closed_caption_renditions();
+
+        assert_eq!(closed_captions.len(), 1);
+        assert_eq!(closed_captions[0].group_id, "cc");
+        assert_eq!(closed_captions[0].uri, None);
+
+        let video = playlist.video_renditions();
+
+        assert_eq!(video.len(), 1);
+        assert_eq!(video[0].name, "Wide");
+        assert_eq!(video[0].language, None);
+    }
+
+    #[test]
+    fn test_rendition_channels() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Stereo\",CHANNELS=\"2\",URI=\"audio/stereo.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Surround\",CHANNELS=\"6\",URI=\"audio/surround.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"ec3\",NAME=\"Atmos\",CHANNELS=\"16/JOC\",URI=\"audio/atmos.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Unknown\",URI=\"audio/unknown.m3u8\"\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,AUDIO=\"aac\"\n",
+            "720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            playlist
+                .audio_renditions()
+                .iter()
+                .map(Rendition::channels)
+                .collect::<Vec<_>>(),
+            vec![Some(2), Some(6), Some(16), None]
+        );
+    }
+
+    #[test]
+    fn test_content_steering() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-CONTENT-STEERING:SERVER-URI=\"https://steering.example.com/manifest.json\",PATHWAY-ID=\"CDN-A\"\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,PATHWAY-ID=\"CDN-A\"\n",
+            "https://a.example.com/720p/index.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,PATHWAY-ID=\"CDN-B\"\n",
+            "https://b.example.com/720p/index.m3u8\n",
+        );
+
+        let playlist = read_playlist(data).unwrap();
+
+        assert_eq!(
+            playlist.content_steering(),
+            Some(ContentSteering {
+                server_uri: "https://steering.example.com/manifest.json".to_owned(),
+                pathway_id: Some("CDN-A".to_owned()),
+            })
+        );
+        assert_eq!(
+            playlist
+                .ext_infos
+                .iter()
+                .filter(|e| e.ext_type == PlaylistExtType::StreamInf)
+                .map(|e| e.pathway_id())
+                .collect::<Vec<_>>(),
+            vec![Some("CDN-A"), Some("CDN-B")]
+        );
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let playlist = read_playlist(&test_file).unwrap();
+
+        assert_eq!(playlist.content_steering(), None);
+    }
+
+    #[test]
+    fn test_iframe_variants() {
+        let test_file = fs::read_to_string("./test_m3u8_files/iframe_playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let iframe_variants = playlist.iframe_variants();
+
+        assert_eq!(playlist.ext_infos.len(), 4);
+        assert_eq!(iframe_variants.len(), 2);
+        assert_eq!(
+            iframe_variants[0].attr("URI").as_deref(),
+            Some("1080p/iframe_index.m3u8")
+        );
+        assert_eq!(
+            iframe_variants[1].attributes.get("BANDWIDTH"),
+            Some(&"93406".into())
+        );
+        assert_eq!(playlist.first_playlist_link(), Some("1080p/index.m3u8"));
+        assert_eq!(
+            parse(&test_file),
+            Ok(PlaylistKind::Master(playlist.clone()))
+        );
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), test_file);
+    }
+
+    #[test]
+    fn test_error_line() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+        let bad_line = test_file
+            .lines()
+            .position(|l| l.starts_with("#EXTINF"))
+            .unwrap()
+            + 3;
+
+        let mut lines = test_file.lines().collect::<Vec<_>>();
+        lines.insert(bad_line - 1, "#EXT-X-DISCONTINUITY-SEQUENCE:abc");
+
+        let data = lines.join("\n");
+        let err = read_media_list(&data).unwrap_err();
+
+        match err {
+            M3U8ParserError::AtLine { line, ref source } => {
+                assert_eq!(line, bad_line);
+                assert!(matches!(**source, M3U8ParserError::ParseIntError(_)));
+            }
+            _ => panic!("expected an AtLine error, got {:?}", err),
+        }
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "error at line {}: ParseInt Error: invalid digit found in string",
+                bad_line
+            )
+        );
+
+        let err = read_playlist("#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\n1.m3u8\n#EXT-X-START:\n")
+            .unwrap_err();
+
+        assert!(matches!(err, M3U8ParserError::AtLine { line: 4, .. }));
+    }
+
+    #[test]
+    fn test_read_from() {
+        let playlist_bytes = fs::read("./test_m3u8_files/playlist.m3u8").unwrap();
+        let media_list_bytes = fs::read("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let playlist = read_playlist_from(&mut std::io::Cursor::new(&playlist_bytes)).unwrap();
+        let media_list =
+            read_media_list_from(&mut std::io::Cursor::new(&media_list_bytes)).unwrap();
+
+        assert_eq!(
+            playlist,
+            read_playlist(std::str::from_utf8(&playlist_bytes).unwrap()).unwrap()
+        );
+        assert_eq!(
+            media_list,
+            read_media_list(std::str::from_utf8(&media_list_bytes).unwrap()).unwrap()
+        );
+
+        let err = read_media_list_from(&mut std::io::Cursor::new(b"#EXTINF:2.0,\n")).unwrap_err();
+
+        assert_eq!(
+            err,
+            M3U8ParserError::AtLine {
+                line: 1,
+                source: Box::new(M3U8ParserError::NomError(
+                    "#EXTINF:2.0,\n".to_owned(),
+                    nom::error::ErrorKind::Tag
+                )),
+            }
+        );
+
+        let err = read_media_list_from(&mut std::io::Cursor::new([0xff, 0xfe])).unwrap_err();
+
+        assert!(matches!(err, M3U8ParserError::IoError(_)));
+    }
+
+    #[test]
+    fn test_save_playlist() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(output, format!("{}\n", test_file));
+
+        let reparsed = read_playlist(&output).unwrap();
+
+        assert_eq!(reparsed.ext_infos.len(), playlist.ext_infos.len());
+        assert_eq!(
+            reparsed.first_playlist_link(),
+            playlist.first_playlist_link()
+        );
+    }
+
+    #[test]
+    fn test_save_playlist_unknown_tags() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+
+        let playlist = read_playlist(&test_file).unwrap();
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+
+        let twitch_info = |s: &str| {
+            s.lines()
+                .find(|l| l.starts_with("#EXT-X-TWITCH-INFO:"))
+                .map(str::to_owned)
+        };
+
+        assert!(twitch_info(&test_file).is_some());
+        assert_eq!(twitch_info(&output), twitch_info(&test_file));
+        assert_eq!(
+            playlist.ext_infos[0].attributes.get("CLUSTER"),
+            Some(&"\"lhr04\"".into())
+        );
+
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TWITCH-ELAPSED-SECS:9016.000\n",
+            "#EXT-X-FOO:A=1,not an attribute\n",
+            "#EXT-X-EMPTY:\n",
+            "#EXTFOO\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+        );
+
+        for read in [read_playlist, read_playlist_strict].iter() {
+            let playlist = read(data).unwrap();
+
+            assert_eq!(
+                playlist.ext_infos[0].attributes.get("UNKNOWN"),
+                Some(&"9016.000".into())
+            );
+
+            let mut output = Vec::new();
+            playlist.save(&mut output).unwrap();
+
+            assert_eq!(String::from_utf8(output).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_filter_by_codec() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"aac/en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"ec3\",NAME=\"English\",URI=\"ec3/en.m3u8\"\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.64001f,mp4a.40.2\",AUDIO=\"aac\"\n",
+            "avc/720p.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=1500000,CODECS=\"hvc1.1.6.L93.B0,ec-3\",AUDIO=\"ec3\"\n",
+            "hevc/720p.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=800000,AUDIO=\"aac\"\n",
+            "unknown/480p.m3u8\n",
+            "#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=200000,CODECS=\"avc1.64001f\",URI=\"avc/iframes.m3u8\"\n",
+            "#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=150000,CODECS=\"hvc1.1.6.L93.B0\",URI=\"hevc/iframes.m3u8\"\n",
+        );
+
+        let playlist = read_playlist(data).unwrap();
+        let filtered = playlist.filter_by_codec(|c| !c.starts_with("hvc1"));
+
+        let uris = filtered
+            .ext_infos
+            .iter()
+            .filter_map(|e| e.attributes.get("URI").map(|u| u.as_ref()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            uris,
+            vec![
+                "\"aac/en.m3u8\"",
+                "avc/720p.m3u8",
+                "unknown/480p.m3u8",
+                "\"avc/iframes.m3u8\""
+            ]
+        );
+        assert_eq!(playlist.ext_infos.len(), 7);
+    }
+
+    #[test]
+    fn test_infer_rendition_codec() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"aac/en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"ec3\",NAME=\"English\",URI=\"ec3/en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"unused\",NAME=\"English\",URI=\"unused/en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"aac\",NAME=\"English\",URI=\"subs/en.m3u8\"\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=800000,AUDIO=\"aac\"\n",
+            "unknown/480p.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.64001f, mp4a.40.2\",AUDIO=\"aac\"\n",
+            "avc/720p.m3u8\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=1500000,CODECS=\"hvc1.1.6.L93.B0,ec-3\",AUDIO=\"ec3\"\n",
+            "hevc/720p.m3u8\n",
+        );
+
+        let playlist = read_playlist(data).unwrap();
+        let codecs = playlist
+            .ext_infos
+            .iter()
+            .filter(|e| e.ext_type == PlaylistExtType::Media)
+            .map(|e| playlist.infer_rendition_codec(e))
+            .collect::<Vec<_>>();
+
+        assert_eq!(codecs, vec![Some("mp4a.40.2"), Some("ec-3"), None, None]);
+    }
+
+    #[test]
+    fn test_audio_rendition_for_language() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Deutsch\",LANGUAGE=\"de\",URI=\"de.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",LANGUAGE=\"en-US\",DEFAULT=YES,URI=\"en.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Francais\",LANGUAGE=\"fr\",URI=\"fr.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"ac3\",NAME=\"Francais\",LANGUAGE=\"fr\",URI=\"ac3/fr.m3u8\"\n",
+            "#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"aac\",NAME=\"Espanol\",LANGUAGE=\"es\",URI=\"es.m3u8\"\n",
+        );
+
+        let playlist = read_playlist(data).unwrap();
+
+        let name = |lang: &str| {
+            playlist
+                .audio_rendition_for_language("aac", lang)
+                .and_then(|r| r.unquoted_attribute("NAME"))
+        };
+
+        assert_eq!(name("FR"), Some("Francais"));
+        assert_eq!(name("en-US"), Some("English"));
+        assert_eq!(name("en"), Some("English"));
+        assert_eq!(name("de-AT"), Some("Deutsch"));
+        assert_eq!(name("es"), Some("English"));
+
+        let first = playlist
+            .audio_rendition_for_language("ac3", "ja")
+            .and_then(|r| r.unquoted_attribute("URI"));
+
+        assert_eq!(first, Some("ac3/fr.m3u8"));
+        assert!(playlist
+            .audio_rendition_for_language("missing", "en")
+            .is_none());
+    }
+
+    #[test]
+    fn test_read_media_list() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(media_list.version, 3);
+        assert_eq!(media_list.target_duration, 6);
+        assert_eq!(media_list.media_sequence, 4508);
+
+        let first_ext_info = media_list.ext_infos.first().unwrap();
+
+        assert_eq!(
+            first_ext_info.ext_type,
+            MediaExtType::Unknown("TWITCH-ELAPSED-SECS".to_owned())
+        );
+        assert_eq!(
+            first_ext_info.attributes.get("UNKNOWN"),
+            Some(&"9016.000".into())
+        );
+
+        let third_ext_info = media_list.ext_infos.get(2).unwrap();
+        assert_eq!(third_ext_info.ext_type, MediaExtType::DateRange,);
+        assert_eq!(
+            third_ext_info.attributes.get("CLASS"),
+            Some(&"\"twitch-stream-source\"".into())
+        );
+        assert_eq!(
+            third_ext_info.attributes.get("START-DATE"),
+            Some(&"\"2020-11-18T14:12:40.956Z\"".into())
+        );
+        assert_eq!(
+            third_ext_info.attributes.get("ID"),
+            Some(&"\"source-1605708760\"".into())
+        );
+        assert_eq!(
+            third_ext_info.attributes.get("END-ON-NEXT"),
+            Some(&"YES".into())
+        );
+        assert_eq!(
+            third_ext_info.attributes.get("X-TV-TWITCH-STREAM-SOURCE"),
+            Some(&"\"live\"".into())
+        );
+
+        let segment_info = media_list.media_segments.first().unwrap();
+
+        assert_eq!(segment_info.duration, 2.000);
+        assert_eq!(segment_info.title, Some("live".to_owned()));
+        assert_eq!(
+            segment_info.uri, "https://video-edge-c6d608.lhr04.abs.hls.ttvnw.net/v1/segment/CrsEzRu838bWUuW9RMJzmO0XYcHHcASBvuHgb5RLq5NVkkDzcl8Fuk-cSTNUnlimYBLSWEbLD-wh6NRTc5dT8O4n-gVAHGHWNDmeFB7fU6uLudKXTvZ6TRUGWGtUg6nEhhyeQpbrxOD7gaK06BzPgGi4tt4N0MCRLxPRFu3a_XnkjkDZ6_Z9F848pp1IVLbogJuKMLeKt-hf4O_zRlI1XEgKM4XzlEhspGfkhoYZ-_L0px96CRUp7rKsYMSLZ6i_KmdXzT7NDm9x76UBKiIUenIf-N6AAgb8BZEDXJ5Vgwi8YxXsFcIaL5W35XFmP4iS9dIwEbneVU-Rn5bIrYxAuCLxq5xRdB-nXUsKr1Vhki11FrOg4Tsk-LYdTsS2X3-Z8NbJytbs8nPzRo3MpQlyWQIw4WshxZqZwQgb6g-jk85eAG6wP4tSCgcbgo4CeADFQIn33ynjYU1VkF7VzQkGNT1sj1HOMtCrET4aDTU8AF9CN_keWBUX7uOjMEpWJDs44GhfjHHmQqQ1kqmmEzPBxYRchWUrhLYNcDQHmJyFDpahCBXl5gKgHK-z7hBaUW8X8STjf7RhtDt9lFgy2b3SL_AVYLXbyMNs6jFAXqOEDe0o5MC0FAXBVgXNSBHEcqkVp0kiu5kL5jGBm37xeYpXxukrcCJ3BUA6w8SQQ9vjMHVgPzRbIL72NGca42z7e0GYGnXGaxqVj08aJtsnI1_U3chdp5k22pSCMpGpuU-lsLRodOomIdrmgkumvN0xXRIQpQ7h-p7kP9_pganXO5M5jhoMer127dU6GU0FarSp.ts"
+        );
+    }
+
+    #[test]
+    fn test_read_media_list_audio_metadata() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXTALB:Greatest Hits\n",
+            "#EXTART:The Band\n",
+            "#EXTGENRE:Rock\n",
+            "#EXTINF:215.000,The Band - First Song\n",
+            "music/first.mp3\n",
+            "#EXTINF:187.500,The Band - Second Song\n",
+            "music/second.mp3\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        let first = media_list.media_segments.first().unwrap();
+        assert_eq!(first.album.as_deref(), Some("Greatest Hits"));
+        assert_eq!(first.artist.as_deref(), Some("The Band"));
+        assert_eq!(first.genre.as_deref(), Some("Rock"));
+        assert_eq!(first.title.as_deref(), Some("The Band - First Song"));
+
+        let second = media_list.media_segments.get(1).unwrap();
+        assert_eq!(second.album, None);
+        assert_eq!(second.artist, None);
+        assert_eq!(second.genre, None);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("#EXTALB:Greatest Hits\n#EXTART:The Band\n#EXTGENRE:Rock\n#EXTINF:215.000,The Band - First Song\n"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(media_list.validate(), Ok(()));
+
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXTINF:6.499,\n",
+            "segment1.ts\n",
+            "#EXTINF:6.500,\n",
+            "segment2.ts\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.validate(),
+            Err(vec![ValidationError::SegmentExceedsTargetDuration {
+                index: 2,
+                duration: 6.5,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_map() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert!(!media_list.requires_map());
+        assert_eq!(media_list.validate_map(), vec![]);
+
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.m4s\n",
+            "#EXTINF:6.000,\n",
+            "segment1.MP4?token=abc\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert!(media_list.requires_map());
+        assert_eq!(
+            media_list.validate_map(),
+            vec![
+                MapIssue::MissingMap { index: 0 },
+                MapIssue::MissingMap { index: 1 },
+            ]
+        );
+
+        let data = data.replace(
+            "#EXTINF:6.000,\nsegment1",
+            "#EXT-X-MAP:URI=\"init.mp4\"\n#EXTINF:6.000,\nsegment1",
+        );
+        let media_list = read_media_list(&data).unwrap();
+
+        assert!(media_list.requires_map());
+        assert_eq!(
+            media_list.validate_map(),
+            vec![MapIssue::MissingMap { index: 0 }]
+        );
+
+        let test_file = fs::read_to_string("./test_m3u8_files/fmp4_media_list.m3u8").unwrap();
+
+        assert_eq!(read_media_list(&test_file).unwrap().validate_map(), vec![]);
+    }
+
+    #[test]
+    fn test_validate_uris() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert_eq!(read_media_list(&test_file).unwrap().validate_uris(), vec![]);
+
+        let mut media_list = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXTINF:6.000,\n",
+            "#EXT-X-DISCONTINUITY\n",
+            "#EXTINF:6.000,\n",
+            "segment 3.ts\n",
+            "#EXTINF:6.000,\n",
+            "https:///segment4.ts\n",
+            "#EXTINF:6.000,\n",
+            "https://cdn.example.com/segment5.ts\n",
+        ))
+        .unwrap();
+
+        // Blank lines are skipped when parsing, so an empty URI can only be set directly.
+        media_list
+            .media_segments
+            .insert(1, MediaSegment::new(Duration::from_secs(6), ""));
+
+        assert_eq!(
+            media_list.validate_uris(),
+            vec![
+                UriIssue::Empty { index: 1 },
+                UriIssue::LooksLikeTag { index: 2 },
+                UriIssue::ContainsWhitespace { index: 3 },
+                UriIssue::MissingHost { index: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_media_list() {
+        let segments = vec![
+            ("segment0.ts".to_owned(), 6.006),
+            ("segment1.ts".to_owned(), 6.006),
+            ("segment2.ts".to_owned(), 3.5),
+        ];
+
+        let media_list = build_media_list(
+            &segments,
+            MediaListBuildOptions {
+                version: 3,
+                media_sequence: 0,
+                end_list: true,
+            },
+        );
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "#EXTM3U\n",
+                "#EXT-X-VERSION:3\n",
+                "#EXT-X-TARGETDURATION:6\n",
+                "#EXT-X-MEDIA-SEQUENCE:0\n",
+                "#EXTINF:6.006,\n",
+                "segment0.ts\n",
+                "#EXTINF:6.006,\n",
+                "segment1.ts\n",
+                "#EXTINF:3.500,\n",
+                "segment2.ts\n",
+                "#EXT-X-ENDLIST\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_media_list_builder() {
+        let segment =
+            |uri: &str, duration| MediaSegment::new(Duration::from_secs_f64(duration), uri);
+
+        let media_list = MediaListBuilder::new()
+            .version(3)
+            .playlist_type(PlaylistType::Vod)
+            .target_duration(10)
+            .push_segment(segment("segment0.ts", 9.009))
+            .push_segment(segment("segment1.ts", 4.5))
+            .end_list()
+            .build();
+
+        assert_eq!(
+            media_list.to_string(),
+            concat!(
+                "#EXTM3U\n",
+                "#EXT-X-VERSION:3\n",
+                "#EXT-X-PLAYLIST-TYPE:VOD\n",
+                "#EXT-X-TARGETDURATION:10\n",
+                "#EXT-X-MEDIA-SEQUENCE:0\n",
+                "#EXTINF:9.009,\n",
+                "segment0.ts\n",
+                "#EXTINF:4.500,\n",
+                "segment1.ts\n",
+                "#EXT-X-ENDLIST\n",
+            )
+        );
+
+        let media_list = MediaListBuilder::new()
+            .media_sequence(7)
+            .push_segment(segment("segment7.ts", 6.006))
+            .build();
+
+        assert_eq!(media_list.version, 0);
+        assert_eq!(media_list.target_duration, 6);
+        assert_eq!(media_list.media_sequence, 7);
+        assert!(!media_list.end_list);
+    }
+
+    #[test]
+    fn test_merge() {
+        let first = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:3\n",
+            "#EXT-X-TARGETDURATION:2\n",
+            "#EXT-X-MEDIA-SEQUENCE:10\n",
+            "#EXT-X-DISCONTINUITY-SEQUENCE:1\n",
+            "#EXTINF:2.000,\n",
+            "segment10.ts?token=a\n",
+            "#EXTINF:2.000,\n",
+            "segment11.ts?token=a\n",
+            "#EXTINF:2.000,\n",
+            "segment12.ts?token=a\n",
+        );
+        let second = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:3\n",
+            "#EXT-X-TARGETDURATION:2\n",
+            "#EXT-X-MEDIA-SEQUENCE:12\n",
+            "#EXT-X-DISCONTINUITY-SEQUENCE:1\n",
+            "#EXTINF:2.000,\n",
+            "segment12.ts?token=b\n",
+            "#EXT-X-DISCONTINUITY\n",
+            "#EXTINF:2.000,\n",
+            "segment13.ts?token=b\n",
+            "#EXTINF:2.000,\n",
+            "segment14.ts?token=b\n",
+            "#EXT-X-ENDLIST\n",
+        );
+
+        let mut media_list = read_media_list(first).unwrap();
+        media_list.merge(read_media_list(second).unwrap());
+
+        assert_eq!(media_list.media_sequence, 10);
+        assert_eq!(media_list.discontinuity_sequence, 1);
+        assert_eq!(
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| s.uri.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "segment10.ts?token=a",
+                "segment11.ts?token=a",
+                "segment12.ts?token=a",
+                "segment13.ts?token=b",
+                "segment14.ts?token=b",
+            ]
+        );
+        assert!(media_list.media_segments[3].discontinuity);
+        assert!(media_list.end_list);
+
+        let mut media_list = read_media_list(first).unwrap();
+        media_list.merge(read_media_list(first).unwrap());
+
+        assert_eq!(media_list.media_segments.len(), 3);
+
+        let gap = second.replace("MEDIA-SEQUENCE:12", "MEDIA-SEQUENCE:20");
+
+        let mut media_list = read_media_list(first).unwrap();
+        media_list.merge(read_media_list(&gap).unwrap());
+
+        assert_eq!(media_list.media_sequence, 20);
+        assert_eq!(media_list.media_segments.len(), 3);
+        assert_eq!(media_list.media_segments[0].uri, "segment12.ts?token=b");
+
+        // The discontinuity before segment 13 scrolls off when merging past it.
+        let mut media_list = read_media_list(second).unwrap();
+        media_list.merge(read_media_list(&gap).unwrap());
+
+        assert_eq!(media_list.media_sequence, 20);
+        assert_eq!(media_list.discontinuity_sequence, 2);
+
+        let mut overflowing = read_media_list(first).unwrap();
+        let mut newer = read_media_list(&gap).unwrap();
+        newer.media_sequence = u32::MAX;
+        newer.skip = Some(Skip {
+            skipped_segments: 5,
+            recently_removed_dateranges: None,
+        });
+        overflowing.merge(newer);
+
+        assert_eq!(overflowing.media_sequence, u32::MAX);
+    }
+
+    #[test]
+    fn test_segment_mut() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+        let segment_count = media_list.media_segments.len();
+
+        media_list.segment_mut(0).unwrap().uri = "https://proxy.example.com/0.ts".to_owned();
+
+        for segment in media_list.segments_mut().skip(1) {
+            segment.title = Some("proxied".to_owned());
+        }
+
+        assert!(media_list.segment_mut(segment_count).is_none());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        let reparsed = read_media_list(std::str::from_utf8(&output).unwrap()).unwrap();
+
+        assert_eq!(
+            reparsed.media_segments[0].uri,
+            "https://proxy.example.com/0.ts"
+        );
+        assert!(reparsed.media_segments[1..]
+            .iter()
+            .all(|s| s.title.as_deref() == Some("proxied")));
+    }
+
+    #[test]
+    fn test_playlist_type() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert_eq!(read_media_list(&test_file).unwrap().playlist_type, None);
+
+        let data = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:0\n#EXTINF:6.000,\nsegment0.ts\n#EXT-X-ENDLIST\n";
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(media_list.playlist_type, Some(PlaylistType::Vod));
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+
+        assert_eq!(
+            read_media_list(&data.replace("VOD", "EVENT"))
+                .unwrap()
+                .playlist_type,
+            Some(PlaylistType::Event)
+        );
+        assert_eq!(
+            read_media_list(&data.replace("VOD", "LIVE")).unwrap_err(),
+            M3U8ParserError::AtLine {
+                line: 3,
+                source: Box::new(M3U8ParserError::InvalidPlaylistType("LIVE".to_owned())),
+            }
+        );
+    }
+
+    #[test]
+    fn test_discontinuity_sequence() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert_eq!(
+            read_media_list(&test_file).unwrap().discontinuity_sequence,
+            0
+        );
+
+        let data = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE: 120 \n#EXT-X-DISCONTINUITY-SEQUENCE: 7 \n#EXTINF:6.000,\nsegment120.ts\n";
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(media_list.media_sequence, 120);
+        assert_eq!(media_list.discontinuity_sequence, 7);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(std::str::from_utf8(&output)
+            .unwrap()
+            .contains("#EXT-X-MEDIA-SEQUENCE:120\n#EXT-X-DISCONTINUITY-SEQUENCE:7\n"));
+    }
+
+    #[test]
+    fn test_discontinuity_positions() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert_eq!(
+            read_media_list(&test_file)
+                .unwrap()
+                .discontinuity_positions(),
+            Vec::<usize>::new()
+        );
+
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        assert_eq!(
+            read_media_list(&test_file)
+                .unwrap()
+                .discontinuity_positions(),
+            vec![0, 8]
+        );
+    }
+
+    #[test]
+    fn test_discontinuity_follows_segment() {
+        let test_file = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:3\n",
+            "#EXT-X-TARGETDURATION:2\n",
+            "#EXTINF:2.000,\n",
+            "first.ts\n",
+            "#EXT-X-DISCONTINUITY\n",
+            "#EXTINF:2.000,\n",
+            "second.ts\n",
+        );
+
+        let mut media_list = read_media_list(test_file).unwrap();
+
+        assert!(!media_list.media_segments[0].discontinuity);
+        assert!(media_list.media_segments[1].discontinuity);
+
+        media_list.media_segments.remove(0);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .ends_with("#EXT-X-DISCONTINUITY\n#EXTINF:2.000,\nsecond.ts\n"));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_program_date_time_parsed() {
+        use chrono::TimeZone;
+
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let segment = &media_list.media_segments[0];
+
+        assert_eq!(
+            segment.program_date_time.as_deref(),
+            Some("2020-11-18T14:13:16.956Z")
+        );
+        assert_eq!(
+            segment.program_date_time_parsed(),
+            Some(
+                chrono::FixedOffset::east_opt(0)
+                    .unwrap()
+                    .with_ymd_and_hms(2020, 11, 18, 14, 13, 16)
+                    .unwrap()
+                    + chrono::Duration::milliseconds(956)
+            )
+        );
+
+        let segment = MediaSegment::new(Duration::from_secs(6), "segment0.ts")
+            .with_program_date_time("2020-11-18T15:13:16.956+0100");
+
+        assert_eq!(
+            segment
+                .program_date_time_parsed()
+                .map(|t| t.timestamp_millis()),
+            Some(1_605_708_796_956)
+        );
+        assert_eq!(
+            MediaSegment::new(Duration::from_secs(6), "segment0.ts")
+                .with_program_date_time("yesterday")
+                .program_date_time_parsed(),
+            None
+        );
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("#EXT-X-PROGRAM-DATE-TIME:2020-11-18T14:13:16.956Z\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_to_json_value() {
+        let test_file = fs::read_to_string("./test_m3u8_files/encrypted_media_list.m3u8").unwrap();
+
+        let json = read_media_list(&test_file).unwrap().to_json_value();
+
+        assert_eq!(json["version"], 5);
+        assert_eq!(json["playlist_type"], serde_json::Value::Null);
+        assert_eq!(json["end_list"], true);
+        assert_eq!(json["segments"].as_array().unwrap().len(), 4);
+        assert_eq!(json["segments"][0]["uri"], "segment0.ts");
+        assert_eq!(json["segments"][0]["duration"], 6.0);
+        assert_eq!(json["segments"][0]["key"]["method"], "AES-128");
+        assert_eq!(
+            json["segments"][2]["key"]["keyformat"],
+            "com.apple.streamingkeydelivery"
+        );
+        assert_eq!(json["segments"][3]["key"], serde_json::Value::Null);
+
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let json = read_media_list(&test_file).unwrap().to_json_value();
+
+        assert_eq!(json["ext_infos"][0]["type"], "-X-TWITCH-ELAPSED-SECS");
+        assert_eq!(
+            json["ext_infos"][2]["attributes"]["CLASS"],
+            "\"twitch-stitched-ad\""
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+        media_list.target_duration = 10;
+
+        assert_eq!(media_list.normalize(NormalizeOptions::default()), vec![]);
+
+        let options = NormalizeOptions {
+            recompute_target_duration: true,
+            sort_attributes: true,
+            remove_detached_discontinuities: true,
+            ..NormalizeOptions::default()
+        };
+
+        assert_eq!(
+            media_list.normalize(options),
+            vec![
+                NormalizeChange::DetachedDiscontinuitiesRemoved { count: 2 },
+                NormalizeChange::AttributesSorted { index: 2 },
+                NormalizeChange::AttributesSorted { index: 3 },
+                NormalizeChange::AttributesSorted { index: 4 },
+                NormalizeChange::AttributesSorted { index: 5 },
+                NormalizeChange::AttributesSorted { index: 6 },
+                NormalizeChange::AttributesSorted { index: 7 },
+                NormalizeChange::AttributesSorted { index: 8 },
+                NormalizeChange::AttributesSorted { index: 9 },
+                NormalizeChange::AttributesSorted { index: 10 },
+                NormalizeChange::TargetDurationChanged {
+                    previous: 10,
+                    current: 2,
+                },
+            ]
+        );
+        assert_eq!(media_list.discontinuity_positions(), vec![0, 8]);
+        assert_eq!(
+            media_list.ext_infos[3].attributes.keys().next(),
+            Some(&"CLASS".into())
+        );
+
+        assert_eq!(media_list.normalize(options), vec![]);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        let reparsed = read_media_list(std::str::from_utf8(&output).unwrap()).unwrap();
+
+        assert_eq!(reparsed.media_segments, media_list.media_segments);
+
+        let test_file =
+            fs::read_to_string("./test_m3u8_files/iframes_only_media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+        media_list.media_segments[2].discontinuity = true;
+
+        let options = NormalizeOptions {
+            realign_discontinuities: true,
+            resolve_byte_range_offsets: true,
+            ..NormalizeOptions::default()
+        };
+
+        assert_eq!(
+            media_list.normalize(options),
+            vec![
+                NormalizeChange::DiscontinuitiesRealigned { count: 1 },
+                NormalizeChange::ByteRangeOffsetResolved {
+                    index: 3,
+                    offset: 1_021_740,
+                },
+            ]
+        );
+        assert_eq!(
+            media_list.media_segments[3].byte_range,
+            Some(ByteRange {
+                length: 8836,
+                offset: Some(1_021_740),
+            })
+        );
+        assert_eq!(
+            media_list
+                .ext_infos
+                .iter()
+                .map(|e| (&e.ext_type, e.position))
+                .collect::<Vec<_>>(),
+            vec![(&MediaExtType::Discontinuity, 2)]
+        );
+
+        assert_eq!(media_list.normalize(options), vec![]);
+
+        let test_file = fs::read_to_string("./test_m3u8_files/encrypted_media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+        let mut key = media_list.media_segments[0].key.clone().unwrap();
+        key.keyformat = Some("identity".to_owned());
+        media_list.media_segments[1].key = Some(key);
+
+        let options = NormalizeOptions {
+            collapse_keys_and_maps: true,
+            ..NormalizeOptions::default()
+        };
+
+        assert_eq!(
+            media_list.normalize(options),
+            vec![NormalizeChange::KeysAndMapsCollapsed { count: 1 }]
+        );
+        assert_eq!(
+            media_list.media_segments[1].key,
+            media_list.media_segments[0].key
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        for path in &[
+            "./test_m3u8_files/twitch_ad_media_list.m3u8",
+            "./test_m3u8_files/encrypted_media_list.m3u8",
+            "./test_m3u8_files/fmp4_media_list.m3u8",
+        ] {
+            let test_file = fs::read_to_string(path).unwrap();
+            let media_list = read_media_list(&test_file).unwrap();
+
+            let json = serde_json::to_string(&media_list).unwrap();
+            let deserialized: MediaList = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(deserialized, media_list);
+        }
+
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let playlist = read_playlist(&test_file).unwrap();
+
+        let json = serde_json::to_string(&playlist).unwrap();
+        let deserialized: Playlist = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, playlist);
+    }
+
+    #[test]
+    fn test_save_vod_media_list() {
+        let test_file = fs::read_to_string("./test_m3u8_files/vod_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(media_list.playlist_type, Some(PlaylistType::Vod));
+        assert!(media_list.end_list);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[1..3],
+            ["#EXT-X-VERSION:3", "#EXT-X-PLAYLIST-TYPE:VOD"]
+        );
+        assert_eq!(lines.last(), Some(&"#EXT-X-ENDLIST"));
+        assert_eq!(output, test_file);
+    }
+
+    #[test]
+    fn test_iframes_only() {
+        let test_file =
+            fs::read_to_string("./test_m3u8_files/iframes_only_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert!(media_list.iframes_only);
+        assert!(media_list.ext_infos.is_empty());
+        assert_eq!(
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| s.byte_range)
+                .collect::<Vec<_>>(),
+            vec![
+                Some(ByteRange {
+                    length: 9400,
+                    offset: Some(376),
+                }),
+                Some(ByteRange {
+                    length: 7144,
+                    offset: Some(478_316),
+                }),
+                Some(ByteRange {
+                    length: 10340,
+                    offset: Some(1_011_400),
+                }),
+                Some(ByteRange {
+                    length: 8836,
+                    offset: None,
+                }),
+            ]
+        );
+        assert!(media_list
+            .media_segments
+            .iter()
+            .all(|s| s.uri == "1080p/main.ts"));
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), test_file);
+
+        let media_list = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-BYTERANGE:9400@376\n",
+            "#EXTINF:4.000,\n",
+            "main.ts\n",
+        ))
+        .unwrap();
+
+        assert!(!media_list.iframes_only);
+        assert_eq!(
+            media_list.media_segments[0].byte_range,
+            Some(ByteRange {
+                length: 9400,
+                offset: Some(376),
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_media_list_with_tag_handler() {
+        #[derive(Debug, PartialEq)]
+        struct AcmeAd {
+            id: String,
+            duration: f64,
+        }
+
+        struct AcmeAdHandler;
+
+        impl TagHandler for AcmeAdHandler {
+            type Output = AcmeAd;
+
+            fn parse(&self, body: &str) -> Option<AcmeAd> {
+                let (id, duration) = body.split_once(',')?;
+
+                Some(AcmeAd {
+                    id: id.to_owned(),
+                    duration: duration.parse().ok()?,
+                })
+            }
+        }
+
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-ACME-AD:ad-1,15.0\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXT-X-ACME-AD:broken\n",
+            "#EXT-X-ACME-OTHER:1\n",
+            "#EXTINF:6.000,\n",
+            "segment1.ts\n",
+        );
+
+        let registry = TagRegistry::new().register("#EXT-X-ACME-AD", AcmeAdHandler);
+        let (media_list, custom_tags) = read_media_list_with(data, &registry).unwrap();
+
+        assert_eq!(
+            custom_tags,
+            vec![CustomTag {
+                tag: "#EXT-X-ACME-AD".to_owned(),
+                position: 0,
+                value: AcmeAd {
+                    id: "ad-1".to_owned(),
+                    duration: 15.0,
+                },
+            }]
+        );
+        assert_eq!(media_list.media_segments.len(), 2);
+        assert_eq!(
+            media_list
+                .ext_infos
+                .iter()
+                .map(|e| (&e.ext_type, e.position))
+                .collect::<Vec<_>>(),
+            vec![
+                (&MediaExtType::Unknown("ACME-AD".to_owned()), 1),
+                (&MediaExtType::Unknown("ACME-OTHER".to_owned()), 1),
+            ]
+        );
+
+        let (media_list, custom_tags) =
+            read_media_list_with(data, &TagRegistry::<AcmeAd>::default()).unwrap();
+
+        assert!(custom_tags.is_empty());
+        assert_eq!(media_list, read_media_list(data).unwrap());
+    }
+
+    #[test]
+    fn test_parse_media_events() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let mut events = Vec::new();
+
+        parse_media_events(&test_file, |event| {
+            events.push(match event {
+                MediaEvent::Segment(_) => "Segment".to_owned(),
+                MediaEvent::ExtInfo(ext_info) => format!("ExtInfo({})", ext_info.ext_type),
+                event => format!("{:?}", event),
+            })
+        })
+        .unwrap();
+
+        let mut expected = vec![
+            "Version(3)",
+            "TargetDuration(6)",
+            "MediaSequence(4508)",
+            "ExtInfo(TWITCH-ELAPSED-SECS)",
+            "ExtInfo(TWITCH-TOTAL-SECS)",
+            "ExtInfo(DATERANGE)",
+            "ExtInfo(DATERANGE)",
+        ];
+        expected.extend(std::iter::repeat_n("Segment", 15));
+
+        assert_eq!(events, expected);
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let mut segments = Vec::new();
+
+        parse_media_events(&test_file, |event| {
+            if let MediaEvent::Segment(segment) = event {
+                segments.push(segment);
+            }
+        })
+        .unwrap();
+
+        assert_eq!(segments, media_list.media_segments);
+
+        assert!(matches!(
+            parse_media_events("#EXTM3U\n#EXT-X-VERSION:x\n", |_| {}),
+            Err(M3U8ParserError::AtLine { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_set_attr() {
+        let mut playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"audio/en.m3u8\"\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000,AUDIO=\"aac\"\n",
+            "720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        playlist.ext_infos[0].set_attr("URI", "https://proxy.example.com/?u=audio/en.m3u8");
+        playlist.ext_infos[0].set_attr("NAME", "English \"Director's cut\"");
+        playlist.ext_infos[1].set_attr("BANDWIDTH", "2500000");
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English \\\"Director's cut\\\"\",URI=\"https://proxy.example.com/?u=audio/en.m3u8\"\n"
+        ));
+        assert!(output.contains("#EXT-X-STREAM-INF:BANDWIDTH=2500000,AUDIO=\"aac\"\n"));
+
+        let reparsed = read_playlist(&output).unwrap();
+
+        assert_eq!(
+            reparsed.ext_infos[0].attr("NAME").as_deref(),
+            Some("English \"Director's cut\"")
+        );
+
+        let mut media_list = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:2\n",
+            "#EXT-X-DATERANGE:ID=\"ad-1\",START-DATE=\"2020-11-25T13:29:10.324Z\",X-AD-URI=\"https://ads.example.com/1\"\n",
+            "#EXTINF:2.000,\n",
+            "segment0.ts\n",
+        ))
+        .unwrap();
+
+        media_list.ext_infos[0].set_attr("X-AD-URI", "https://proxy.example.com/ads/1");
+        media_list.ext_infos[0].set_attr("DURATION", "2.0");
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("X-AD-URI=\"https://proxy.example.com/ads/1\",DURATION=2.0\n"));
+    }
+
+    #[test]
+    fn test_end_list() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+
+        assert!(!media_list.end_list);
+        assert!(media_list.is_live());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(!std::str::from_utf8(&output)
+            .unwrap()
+            .contains("#EXT-X-ENDLIST"));
+
+        media_list.end_list = true;
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        let output = std::str::from_utf8(&output).unwrap();
+
+        assert!(output.ends_with("#EXT-X-ENDLIST\n"));
+
+        let media_list = read_media_list(output).unwrap();
+
+        assert!(media_list.end_list);
+        assert!(!media_list.is_live());
+    }
+
+    #[test]
+    fn test_start() {
+        let media_list_file = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:6\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-START:TIME-OFFSET=-30.5,PRECISE=YES\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+        );
+
+        let media_list = read_media_list(media_list_file).unwrap();
+
+        assert_eq!(
+            media_list.start,
+            Some(Start {
+                time_offset: -30.5,
+                precise: true,
+            })
+        );
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), media_list_file);
+
+        let playlist_file = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-START:TIME-OFFSET=10\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+        );
+
+        let playlist = read_playlist(playlist_file).unwrap();
+
+        assert_eq!(
+            playlist.start,
+            Some(Start {
+                time_offset: 10.0,
+                precise: false,
+            })
+        );
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), playlist_file);
+
+        assert!(matches!(
+            read_playlist("#EXTM3U\n#EXT-X-START:PRECISE=YES\n"),
+            Err(M3U8ParserError::AtLine { line: 2, source }) if matches!(*source, M3U8ParserError::ParseFloatError(_))
+        ));
+    }
+
+    #[test]
+    fn test_gap() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:8\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-GAP\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXTINF:6.000,\n",
+            "segment1.ts\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert!(media_list.media_segments[0].gap);
+        assert!(!media_list.media_segments[1].gap);
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+    }
+
+    #[test]
+    fn test_bitrate() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:8\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXT-X-BITRATE:1500\n",
+            "#EXTINF:6.000,\n",
+            "segment1.ts\n",
+            "#EXTINF:6.000,\n",
+            "segment2.ts\n",
+            "#EXT-X-BITRATE:3000\n",
+            "#EXTINF:6.000,\n",
+            "segment3.ts\n",
+            "#EXTINF:6.000,\n",
+            "segment4.ts\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| s.bitrate)
+                .collect::<Vec<_>>(),
+            vec![None, Some(1500), Some(1500), Some(3000), Some(3000)]
+        );
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+    }
+
+    #[test]
+    fn test_parts() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:9\n",
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-PART:DURATION=2,URI=\"segment0.part0.mp4\",INDEPENDENT=YES\n",
+            "#EXT-X-PART:DURATION=1.5,URI=\"segment0.mp4\",BYTERANGE=\"1000@500\"\n",
+            "#EXTINF:3.500,\n",
+            "segment0.mp4\n",
+            "#EXTINF:4.000,\n",
+            "segment1.mp4\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.media_segments[0].parts,
+            vec![
+                Part {
+                    duration: 2.0,
+                    uri: "segment0.part0.mp4".to_owned(),
+                    independent: true,
+                    byte_range: None,
+                },
+                Part {
+                    duration: 1.5,
+                    uri: "segment0.mp4".to_owned(),
+                    independent: false,
+                    byte_range: Some(ByteRange {
+                        length: 1000,
+                        offset: Some(500),
+                    }),
+                },
+            ]
+        );
+        assert!(media_list.media_segments[1].parts.is_empty());
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+    }
+
+    #[test]
+    fn test_preload_hints() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:9\n",
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-PART:DURATION=2,URI=\"segment0.part0.mp4\",INDEPENDENT=YES\n",
+            "#EXT-X-PART:DURATION=2,URI=\"segment0.part1.mp4\"\n",
+            "#EXTINF:4.000,\n",
+            "segment0.mp4\n",
+            "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment1.part0.mp4\",BYTERANGE-START=0\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.preload_hints,
+            vec![PreloadHint {
+                hint_type: "PART".to_owned(),
+                uri: "segment1.part0.mp4".to_owned(),
+                byterange_start: Some(0),
+                byterange_length: None,
+            }]
+        );
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+
+        assert!(media_list.window(0, 0).preload_hints.is_empty());
+        assert_eq!(media_list.window(0, 1).preload_hints.len(), 1);
+    }
+
+    #[test]
+    fn test_rendition_reports() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:9\n",
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-MEDIA-SEQUENCE:266\n",
+            "#EXTINF:4.000,\n",
+            "segment266.mp4\n",
+            "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment267.part0.mp4\"\n",
+            "#EXT-X-RENDITION-REPORT:URI=\"../720p/index.m3u8\",LAST-MSN=266,LAST-PART=2\n",
+            "#EXT-X-RENDITION-REPORT:URI=\"../480p/index.m3u8\",LAST-MSN=265,LAST-PART=3\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list
+                .rendition_reports
+                .iter()
+                .map(|r| (r.uri.as_str(), r.last_msn, r.last_part))
+                .collect::<Vec<_>>(),
+            vec![
+                ("../720p/index.m3u8", Some(266), Some(2)),
+                ("../480p/index.m3u8", Some(265), Some(3)),
+            ]
+        );
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+    }
+
+    #[test]
+    fn test_substitute_variables() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:8\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-DEFINE:NAME=\"cdn\",VALUE=\"https://cdn.example.com\"\n",
+            "#EXT-X-DEFINE:QUERYPARAM=\"token\"\n",
+            "#EXT-X-KEY:METHOD=AES-128,URI=\"{$cdn}/key\"\n",
+            "#EXTINF:6.000,\n",
+            "{$cdn}/live/segment0.ts\n",
+            "#EXTINF:6.000,\n",
+            "{$cdn}/live/segment1.ts\n",
+        );
+
+        let mut media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.defines.get("cdn").map(String::as_str),
+            Some("https://cdn.example.com")
+        );
+        assert_eq!(media_list.ext_infos.len(), 1);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+
+        media_list.substitute_variables().unwrap();
+
+        assert_eq!(
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| s.uri.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://cdn.example.com/live/segment0.ts",
+                "https://cdn.example.com/live/segment1.ts",
+            ]
+        );
+        assert_eq!(
+            media_list.media_segments[1]
+                .key
+                .as_ref()
+                .and_then(|k| k.uri.as_deref()),
+            Some("https://cdn.example.com/key")
+        );
+
+        let undefined = data.replace("{$cdn}/live", "{$token}/live");
+        let mut media_list = read_media_list(&undefined).unwrap();
+
+        assert_eq!(
+            media_list.substitute_variables(),
+            Err(M3U8ParserError::UndefinedVariable("token".to_owned()))
+        );
+
+        let mut playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-DEFINE:NAME=\"cdn\",VALUE=\"https://cdn.example.com\"\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "{$cdn}/720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        playlist.substitute_variables().unwrap();
+
+        assert_eq!(
+            playlist.first_playlist_link(),
+            Some("https://cdn.example.com/720p/index.m3u8")
+        );
+        assert_eq!(
+            substitute("{$}{$a b}{$cdn", &playlist.defines),
+            Ok("{$}{$a b}{$cdn".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_server_control() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:9\n",
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-SERVER-CONTROL:CAN-SKIP-UNTIL=24,CAN-SKIP-DATERANGES=YES,HOLD-BACK=12,",
+            "PART-HOLD-BACK=1.002,CAN-BLOCK-RELOAD=YES\n",
+            "#EXTINF:4.000,\n",
+            "segment0.mp4\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.server_control,
+            Some(ServerControl {
+                can_skip_until: Some(24.0),
+                can_skip_dateranges: true,
+                hold_back: Some(12.0),
+                part_hold_back: Some(1.002),
+                can_block_reload: true,
+            })
+        );
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+
+        let data = data.replace("CAN-BLOCK-RELOAD=YES", "CAN-BLOCK-RELOAD=NO");
+
+        assert!(
+            !read_media_list(&data)
+                .unwrap()
+                .server_control
+                .unwrap()
+                .can_block_reload
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:9\n",
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-MEDIA-SEQUENCE:100\n",
+            "#EXT-X-SKIP:SKIPPED-SEGMENTS=12,RECENTLY-REMOVED-DATERANGES=\"ad-1\tad-2\"\n",
+            "#EXTINF:4.000,\n",
+            "segment112.mp4\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+        let skip = media_list.skip.as_ref().unwrap();
+
+        assert_eq!(skip.skipped_segments, 12);
+        assert_eq!(
+            skip.recently_removed_dateranges.as_deref(),
+            Some("ad-1\tad-2")
+        );
+        assert_eq!(media_list.media_segments.len(), 1);
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+    }
+
+    #[test]
+    fn test_independent_segments() {
+        let media_list_file = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-VERSION:6\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:0\n",
+            "#EXT-X-INDEPENDENT-SEGMENTS\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+        );
+
+        let media_list = read_media_list(media_list_file).unwrap();
+
+        assert!(media_list.independent_segments);
+        assert!(media_list.ext_infos.is_empty());
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), media_list_file);
+
+        let playlist_file = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-INDEPENDENT-SEGMENTS\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+        );
+
+        let playlist = read_playlist(playlist_file).unwrap();
+
+        assert!(playlist.independent_segments);
+        assert_eq!(playlist.ext_infos.len(), 1);
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), playlist_file);
+
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        assert!(!read_media_list(&test_file).unwrap().independent_segments);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let mut reloaded = read_media_list(&test_file).unwrap();
+
+        assert_eq!(media_list.content_hash(), reloaded.content_hash());
+
+        reloaded.media_sequence += 1;
+
+        assert_ne!(media_list.content_hash(), reloaded.content_hash());
+
+        reloaded.media_sequence -= 1;
+        reloaded.media_segments.pop();
+
+        assert_ne!(media_list.content_hash(), reloaded.content_hash());
+    }
+
+    #[test]
+    fn test_total_duration() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        let expected = media_list
+            .media_segments
+            .iter()
+            .map(|s| s.duration)
+            .sum::<f64>();
+
+        assert_eq!(media_list.segment_count(), media_list.media_segments.len());
+        assert!((media_list.total_duration() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_segment_sequence() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let last_index = media_list.media_segments.len() - 1;
+
+        assert_eq!(media_list.media_sequence, 4508);
+        assert_eq!(media_list.segment_sequence(0), Some(4508));
+        assert_eq!(media_list.segment_sequence(2), Some(4510));
+        assert_eq!(media_list.segment_sequence(last_index + 1), None);
+
+        assert_eq!(
+            media_list.segment_by_sequence(4510),
+            media_list.media_segments.get(2)
+        );
+        assert_eq!(
+            media_list.segment_by_sequence(4508 + last_index as u32),
+            media_list.media_segments.last()
+        );
+        assert_eq!(media_list.segment_by_sequence(4507), None);
+        assert_eq!(
+            media_list.segment_by_sequence(4508 + last_index as u32 + 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_duration_as_std() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(media_list.media_segments[0].duration, 2.0);
+        assert_eq!(
+            media_list.media_segments[0].duration_as_std(),
+            Duration::from_secs(2)
+        );
+
+        let mut segment = MediaSegment::new(Duration::from_millis(6006), "segment0.ts");
+
+        assert_eq!(segment.duration_as_std(), Duration::from_millis(6006));
+
+        for invalid in [-1.0, f64::NAN, f64::INFINITY].iter() {
+            segment.duration = *invalid;
+
+            assert_eq!(segment.duration_as_std(), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_total_duration_compensated() {
+        let segments = (0..10_000)
+            .map(|i| (format!("segment{}.ts", i), 2.002))
+            .collect::<Vec<_>>();
+
+        let media_list = build_media_list(&segments, MediaListBuildOptions::default());
+
+        assert!((media_list.total_duration() - 20_020.0).abs() < 0.001);
+        assert_eq!(compensated_sum(vec![1.0, 1e100, 1.0, -1e100]), 2.0);
+    }
+
+    #[test]
+    fn test_extend_media_list() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+        let segment_count = media_list.media_segments.len();
+
+        let segment =
+            |duration: f64, uri: &str| MediaSegment::new(Duration::from_secs_f64(duration), uri);
+
+        media_list.extend(vec![segment(2.0, "a.ts"), segment(5.9, "b.ts")]);
+
+        assert_eq!(media_list.media_segments.len(), segment_count + 2);
+        assert_eq!(media_list.media_segments.last().unwrap().uri, "b.ts");
+        assert_eq!(media_list.target_duration, 6);
+
+        media_list.extend(vec![segment(9.6, "c.ts")]);
+
+        assert_eq!(media_list.target_duration, 10);
+
+        media_list.media_segments.pop();
+        media_list.recompute_target_duration();
+
+        assert_eq!(media_list.target_duration, 6);
+    }
+
+    #[test]
+    fn test_header_template() {
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let template = media_list.header_template();
+
+        assert_eq!(template.version, 3);
+        assert_eq!(template.target_duration, 5);
+        assert_eq!(template.media_sequence, media_list.media_sequence);
+        assert_eq!(template.line_ending, media_list.line_ending);
+        assert!(template.media_segments.is_empty());
+        assert_eq!(
+            template
+                .ext_infos
+                .iter()
+                .map(|e| e.ext_type.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                MediaExtType::Unknown("TWITCH-ELAPSED-SECS".to_owned()),
+                MediaExtType::Unknown("TWITCH-TOTAL-SECS".to_owned()),
+            ]
+        );
+        assert_eq!(template.start, media_list.start);
+    }
+
+    #[test]
+    fn test_read_media_list_keys() {
+        let test_file = fs::read_to_string("./test_m3u8_files/encrypted_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let keys = media_list
+            .media_segments
+            .iter()
+            .map(|s| s.key.as_ref())
+            .collect::<Vec<_>>();
+
+        let aes_128 = Key {
+            method: KeyMethod::Aes128,
+            uri: Some("https://example.com/key0".to_owned()),
+            iv: Some("0x00000000000000000000000000000001".to_owned()),
+            keyformat: None,
+            keyformatversions: None,
+        };
+
+        assert_eq!(keys[0], Some(&aes_128));
+        assert_eq!(keys[1], Some(&aes_128));
+        assert_eq!(keys[2].unwrap().method, KeyMethod::SampleAes);
+        assert_eq!(
+            keys[2].unwrap().keyformat.as_deref(),
+            Some("com.apple.streamingkeydelivery")
+        );
+        assert_eq!(keys[3], None);
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), test_file);
+
+        let window = media_list.window(1, 2);
+        let mut output = Vec::new();
+        window.save(&mut output).unwrap();
+
+        assert!(std::str::from_utf8(&output)
+            .unwrap()
+            .contains("#EXT-X-KEY:METHOD=AES-128,URI=\"https://example.com/key0\""));
+
+        assert_eq!(
+            read_media_list("#EXTM3U\n#EXT-X-KEY:METHOD=AES-256\n").unwrap_err(),
+            M3U8ParserError::AtLine {
+                line: 2,
+                source: Box::new(M3U8ParserError::InvalidKeyMethod("AES-256".to_owned())),
+            }
+        );
+    }
+
+    #[test]
+    fn test_key_system() {
+        let key = |keyformat: Option<&str>| Key {
+            method: KeyMethod::SampleAes,
+            uri: None,
+            iv: None,
+            keyformat: keyformat.map(|f| f.to_owned()),
+            keyformatversions: None,
+        };
+
+        assert!(key(None).is_identity());
+        assert!(key(Some("identity")).is_identity());
+        assert!(!key(Some("com.apple.streamingkeydelivery")).is_identity());
+        assert_eq!(
+            key(None).key_system(),
+            KeySystem::Other("identity".to_owned())
+        );
+
+        for (keyformat, key_system) in &[
+            (
+                "urn:uuid:e2719d58-a985-b3c9-781a-b030af78d30e",
+                KeySystem::ClearKey,
+            ),
+            (
+                "urn:uuid:EDEF8BA9-79D6-4ACE-A3C8-27DCD51D21ED",
+                KeySystem::Widevine,
+            ),
+            ("com.apple.streamingkeydelivery", KeySystem::FairPlay),
+            (
+                "urn:uuid:9a04f079-9840-4286-ab92-e65be0885f95",
+                KeySystem::PlayReady,
+            ),
+            (
+                "urn:uuid:00000000-0000-0000-0000-000000000000",
+                KeySystem::Other("urn:uuid:00000000-0000-0000-0000-000000000000".to_owned()),
+            ),
+        ] {
+            assert_eq!(key(Some(keyformat)).key_system(), *key_system);
+        }
+    }
+
+    #[test]
+    fn test_read_media_list_map() {
+        let test_file = fs::read_to_string("./test_m3u8_files/fmp4_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+        let map = MapInfo {
+            uri: "init.mp4".to_owned(),
+            byte_range: Some(ByteRange {
+                length: 720,
+                offset: Some(0),
+            }),
+        };
+
+        assert!(media_list
+            .media_segments
+            .iter()
+            .all(|s| s.map.as_ref() == Some(&map)));
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), test_file);
+
+        let window = media_list.window(2, 1);
+
+        assert_eq!(window.media_segments[0].map.as_ref(), Some(&map));
+
+        assert!(
+            read_media_list("#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\",BYTERANGE=\"720@\"\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_window() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:100\n",
+            "#EXT-X-DISCONTINUITY-SEQUENCE:3\n",
+            "#EXTINF:6.000,\nsegment100.ts\n",
+            "#EXT-X-DISCONTINUITY\n",
+            "#EXTINF:6.000,\nsegment101.ts\n",
+            "#EXT-X-PROGRAM-DATE-TIME:2020-11-18T14:12:40.956Z\n",
+            "#EXTINF:6.000,\nsegment102.ts\n",
+            "#EXTINF:6.000,\nsegment103.ts\n",
+            "#EXT-X-ENDLIST\n",
+        );
+
+        let media_list = read_media_list(data).unwrap();
+        let window = media_list.window(102, 2);
+
+        assert_eq!(window.media_sequence, 102);
+        assert_eq!(window.discontinuity_sequence, 4);
+        assert_eq!(window.media_segments, media_list.media_segments[2..4]);
+        assert_eq!(
+            window.media_segments[0].program_date_time.as_deref(),
+            Some("2020-11-18T14:12:40.956Z")
+        );
+        assert!(window.end_list);
+        assert!(window
+            .ext_infos
+            .iter()
+            .all(|e| e.ext_type != MediaExtType::Discontinuity));
+
+        let window = media_list.window(90, 12);
+
+        assert_eq!(window.media_sequence, 100);
+        assert_eq!(window.media_segments.len(), 2);
+        assert!(window.media_segments[1].discontinuity);
+        assert!(!window.end_list);
+
+        assert!(media_list.window(200, 2).media_segments.is_empty());
+
+        let mut media_list = media_list;
+        media_list.media_sequence = u32::MAX;
+        media_list.discontinuity_sequence = u32::MAX;
+
+        let window = media_list.window(u64::from(u32::MAX) + 2, 1);
+
+        assert_eq!(window.media_sequence, u32::MAX);
+        assert_eq!(window.discontinuity_sequence, u32::MAX);
+        assert_eq!(window.media_segments, media_list.media_segments[2..3]);
+    }
+
+    #[test]
+    fn test_validate_sequence_consistency() {
+        let previous = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-MEDIA-SEQUENCE:10\n",
+            "#EXT-X-DISCONTINUITY-SEQUENCE:2\n",
+            "#EXTINF:6.000,\n",
+            "segment10.ts\n",
+            "#EXT-X-DISCONTINUITY\n",
+            "#EXTINF:6.000,\n",
+            "segment11.ts\n",
+            "#EXTINF:6.000,\n",
+            "segment12.ts\n",
+        ))
+        .unwrap();
+
+        let current = |media_sequence: u32, discontinuity_sequence: u32| {
+            format!(
+                "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:{}\n#EXT-X-DISCONTINUITY-SEQUENCE:{}\n#EXTINF:6.000,\nsegment.ts\n",
+                media_sequence, discontinuity_sequence
+            )
+        };
+
+        let consistent = current(12, 3);
+        assert_eq!(
+            read_media_list(&consistent)
+                .unwrap()
+                .validate_sequence_consistency(&previous),
+            vec![]
+        );
+
+        let unchanged = current(11, 2);
+        assert_eq!(
+            read_media_list(&unchanged)
+                .unwrap()
+                .validate_sequence_consistency(&previous),
+            vec![]
+        );
+
+        let stale = current(12, 2);
+        assert_eq!(
+            read_media_list(&stale)
+                .unwrap()
+                .validate_sequence_consistency(&previous),
+            vec![SequenceIssue::DiscontinuitySequenceMismatch {
+                expected: 3,
+                actual: 2
+            }]
+        );
+
+        let backwards = current(9, 2);
+        assert_eq!(
+            read_media_list(&backwards)
+                .unwrap()
+                .validate_sequence_consistency(&previous),
+            vec![SequenceIssue::MediaSequenceDecreased {
+                previous: 10,
+                current: 9
+            }]
+        );
+
+        let too_old = current(15, 3);
+        assert_eq!(
+            read_media_list(&too_old)
+                .unwrap()
+                .validate_sequence_consistency(&previous),
+            vec![SequenceIssue::SnapshotTooOld {
+                missing_segments: 2
+            }]
+        );
+
+        let mut previous = previous;
+        previous.discontinuity_sequence = u32::MAX;
+
+        assert_eq!(
+            read_media_list(&consistent)
+                .unwrap()
+                .validate_sequence_consistency(&previous),
+            vec![SequenceIssue::DiscontinuitySequenceOverflowed {
+                previous: u32::MAX,
+                removed_discontinuities: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_read_bom() {
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let bom_file = format!("\u{FEFF}{}", test_file);
+
+        let playlist = read_playlist(&test_file).unwrap();
+        let bom_playlist = read_playlist(&bom_file).unwrap();
+
+        assert_eq!(bom_playlist.ext_infos.len(), playlist.ext_infos.len());
+
+        for (bom, plain) in bom_playlist.ext_infos.iter().zip(&playlist.ext_infos) {
+            assert_eq!(bom.ext_type, plain.ext_type);
+            assert_eq!(bom.attributes, plain.attributes);
+        }
+
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+        let bom_file = format!("\u{FEFF}{}", test_file);
+
+        assert_eq!(
+            read_media_list(&bom_file).unwrap().media_segments,
+            read_media_list(&test_file).unwrap().media_segments
+        );
+    }
+
+    #[test]
+    fn test_read_crlf() {
+        let crlf_file = fs::read_to_string("./test_m3u8_files/crlf_media_list.m3u8").unwrap();
+        let lf_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let crlf_media_list = read_media_list(&crlf_file).unwrap();
+        let lf_media_list = read_media_list(&lf_file).unwrap();
+
+        assert_eq!(crlf_media_list.version, lf_media_list.version);
+        assert_eq!(
+            crlf_media_list.target_duration,
+            lf_media_list.target_duration
+        );
+        assert_eq!(crlf_media_list.media_sequence, lf_media_list.media_sequence);
+        assert_eq!(crlf_media_list.media_segments, lf_media_list.media_segments);
+        assert_eq!(
+            crlf_media_list.ext_infos.len(),
+            lf_media_list.ext_infos.len()
+        );
+
+        for (crlf, lf) in crlf_media_list
+            .ext_infos
+            .iter()
+            .zip(&lf_media_list.ext_infos)
+        {
+            assert_eq!(crlf.ext_type, lf.ext_type);
+            assert_eq!(crlf.attributes, lf.attributes);
+        }
+
+        let lf_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let crlf_file = lf_file.replace('\n', "\r\n");
+
+        let crlf_playlist = read_playlist(&crlf_file).unwrap();
+        let lf_playlist = read_playlist(&lf_file).unwrap();
+
+        assert_eq!(crlf_playlist.ext_infos.len(), lf_playlist.ext_infos.len());
+
+        for (crlf, lf) in crlf_playlist.ext_infos.iter().zip(&lf_playlist.ext_infos) {
+            assert_eq!(crlf.ext_type, lf.ext_type);
+            assert_eq!(crlf.attributes, lf.attributes);
+        }
+    }
+
+    #[test]
+    fn test_save_media_list_crlf() {
+        let crlf_file = fs::read_to_string("./test_m3u8_files/crlf_media_list.m3u8").unwrap();
+        let lf_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let crlf_media_list = read_media_list(&crlf_file).unwrap();
+        let lf_media_list = read_media_list(&lf_file).unwrap();
+
+        assert_eq!(crlf_media_list.line_ending, LineEnding::CrLf);
+        assert_eq!(lf_media_list.line_ending, LineEnding::Lf);
+
+        let mut crlf_output = Vec::new();
+        crlf_media_list.save(&mut crlf_output).unwrap();
+
+        let mut lf_output = Vec::new();
+        lf_media_list.save(&mut lf_output).unwrap();
+
+        let crlf_output = String::from_utf8(crlf_output).unwrap();
+        let lf_output = String::from_utf8(lf_output).unwrap();
+
+        assert_eq!(crlf_output, lf_output.replace('\n', "\r\n"));
+
+        let mut overridden_output = Vec::new();
+        crlf_media_list
+            .save_with(
+                &mut overridden_output,
+                SaveOptions {
+                    line_ending: Some(LineEnding::Lf),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(String::from_utf8(overridden_output).unwrap(), lf_output);
+    }
+
+    #[test]
+    fn test_save_hoists_header_tags() {
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"aac/en.m3u8\"\n",
+            "#EXT-X-DEFINE:NAME=\"host\",VALUE=\"example.com\"\n",
+            "#EXT-X-START:TIME-OFFSET=10\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=2000000,AUDIO=\"aac\"\n",
+            "720p.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            playlist.to_string(),
+            concat!(
+                "#EXTM3U\n",
+                "#EXT-X-START:TIME-OFFSET=10\n",
+                "#EXT-X-DEFINE:NAME=\"host\",VALUE=\"example.com\"\n",
+                "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"aac/en.m3u8\"\n",
+                "#EXT-X-STREAM-INF:BANDWIDTH=2000000,AUDIO=\"aac\"\n",
+                "720p.m3u8\n",
+            )
+        );
+
+        let media_list = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXT-X-DEFINE:NAME=\"host\",VALUE=\"example.com\"\n",
+            "#EXT-X-START:TIME-OFFSET=10\n",
+            "#EXTINF:6.000,\n",
+            "segment1.ts\n",
+        ))
+        .unwrap();
+
+        let output = media_list.to_string();
+        let line = |prefix: &str| output.lines().position(|l| l.starts_with(prefix));
+
+        assert!(line("#EXT-X-START").unwrap() < line("#EXTINF").unwrap());
+        assert!(line("#EXT-X-DEFINE").unwrap() < line("#EXTINF").unwrap());
+    }
+
+    #[test]
+    fn test_save_playlist_crlf() {
+        let lf_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let crlf_file = lf_file.replace('\n', "\r\n");
+
+        let crlf_playlist = read_playlist(&crlf_file).unwrap();
+
+        assert_eq!(crlf_playlist.line_ending, LineEnding::CrLf);
+        assert_eq!(read_playlist(&lf_file).unwrap().line_ending, LineEnding::Lf);
+
+        let mut crlf_output = Vec::new();
+        crlf_playlist.save(&mut crlf_output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(crlf_output).unwrap(),
+            format!("{}\r\n", crlf_file)
+        );
+        assert_eq!(crlf_playlist.to_string(), format!("{}\r\n", crlf_file));
+
+        let mut overridden_output = Vec::new();
+        crlf_playlist
+            .save_with(
+                &mut overridden_output,
+                SaveOptions {
+                    line_ending: Some(LineEnding::Lf),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(overridden_output).unwrap(),
+            format!("{}\n", lf_file)
+        );
+    }
+
+    #[test]
+    fn test_ad_breaks() {
+        let test_file = fs::read_to_string("./test_m3u8_files/cue_ad_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(
+            media_list.ad_breaks(),
+            vec![
+                AdBreak {
+                    start_segment: 1,
+                    end_segment: 3,
+                    duration: Some(12.0),
+                },
+                AdBreak {
+                    start_segment: 4,
+                    end_segment: 5,
+                    duration: Some(6.0),
+                },
+            ]
+        );
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(
+            read_media_list(std::str::from_utf8(&output).unwrap())
+                .unwrap()
+                .media_segments,
+            media_list.media_segments
+        );
+
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(
+            media_list.ad_breaks(),
+            vec![AdBreak {
+                start_segment: 0,
+                end_segment: 8,
+                duration: Some(15.05),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_string() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+        let media_list = read_media_list(&test_file).unwrap();
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(media_list.to_string(), String::from_utf8(output).unwrap());
+
+        let crlf = test_file.replace('\n', "\r\n");
+        let media_list = read_media_list(&crlf).unwrap();
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(media_list.to_string(), String::from_utf8(output).unwrap());
+
+        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+        let playlist = read_playlist(&test_file).unwrap();
+
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(playlist.to_string(), String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn test_save_keeps_ext_info_order() {
+        let test_file = fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let media_list = read_media_list(&test_file).unwrap();
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+
+        // EXT-X-START is typed and written in the header, so only its position may change.
+        let without_start = |m3u8: &str| {
+            m3u8.lines()
+                .filter(|l| !l.starts_with("#EXT-X-START:"))
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(without_start(&output), without_start(&test_file));
+    }
+
+    #[test]
+    fn test_save_media_list() {
+        let curr_stream =
+            fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&curr_stream).unwrap();
+
+        let ad_breaks = media_list.ad_breaks();
+        let in_ad_break = |index: usize| {
+            ad_breaks
+                .iter()
+                .any(|b| (b.start_segment..b.end_segment).contains(&index))
+        };
+
+        // Move the remaining tags along with the segments they were read between.
+        for ext_info in &mut media_list.ext_infos {
+            ext_info.position = (0..ext_info.position).filter(|&i| !in_ad_break(i)).count();
+        }
+
+        let mut index = 0;
+
+        media_list.media_segments.retain(|_| {
+            index += 1;
+
+            !in_ad_break(index - 1)
+        });
+
+        media_list.ext_infos.retain(|e| {
+            e.attributes.get("CLASS").map_or("", |c| c.as_ref()) != "\"twitch-ad-quartile\""
+                && e.attributes.get("CLASS").map_or("", |c| c.as_ref()) != "\"twitch-stitched-ad\""
+                && !e
+                    .attributes
+                    .get("X-TV-TWITCH-STREAM-SOURCE")
+                    .map_or("", |s| s.as_ref())
+                    .starts_with("\"Amazon")
+        });
+        media_list.start = None;
+
+        // Discontinuities are written from the segments, so the ones around the dropped ad
+        // breaks are cleared there.
+        for segment in &mut media_list.media_segments {
+            segment.discontinuity = false;
+        }
+
+        let saved_stream =
+            fs::read_to_string("./test_m3u8_files/save_twitch_ad_media_list.m3u8").unwrap();
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), saved_stream);
+    }
+
+    #[test]
+    fn test_as_stream_inf() {
+        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+
+        let mut media_list = read_media_list(&test_file).unwrap();
+
+        assert_eq!(
+            media_list
+                .as_stream_inf("720p60/index.m3u8", 3_000_000)
+                .attributes
+                .get("AVERAGE-BANDWIDTH"),
+            None
+        );
+
+        for (i, segment) in media_list.media_segments.iter_mut().enumerate() {
+            segment.bitrate = Some(if i == 0 { 3000 } else { 2000 });
+        }
+
+        let stream_inf = media_list.as_stream_inf("720p60/index.m3u8", 3_000_000);
+
+        assert_eq!(stream_inf.ext_type, PlaylistExtType::StreamInf);
+        assert_eq!(
+            stream_inf.attributes.get("BANDWIDTH"),
+            Some(&"3000000".into())
+        );
+        assert_eq!(
+            stream_inf.attributes.get("AVERAGE-BANDWIDTH"),
+            Some(&"2066667".into())
+        );
+
+        let test_file =
+            fs::read_to_string("./test_m3u8_files/iframes_only_media_list.m3u8").unwrap();
+
+        let iframe_stream_inf = read_media_list(&test_file)
+            .unwrap()
+            .as_stream_inf("720p60/iframes.m3u8", 200_000);
+
+        assert_eq!(iframe_stream_inf.ext_type, PlaylistExtType::IFrameStreamInf);
+        assert_eq!(
+            iframe_stream_inf.attributes.get("URI"),
+            Some(&"\"720p60/iframes.m3u8\"".into())
+        );
+
+        let test_file = fs::read_to_string("./test_m3u8_files/fmp4_media_list.m3u8").unwrap();
+
+        let fmp4_stream_inf = read_media_list(&test_file)
+            .unwrap()
+            .as_stream_inf("720p60/fmp4.m3u8", 3_000_000);
+
+        assert!(fmp4_stream_inf.attributes.get("RESOLUTION").is_none());
+        assert!(fmp4_stream_inf.attributes.get("CODECS").is_none());
+
+        let playlist = Playlist {
+            ext_infos: vec![stream_inf],
+            independent_segments: false,
+            start: None,
+            defines: IndexMap::new(),
+            line_ending: LineEnding::Lf,
+        };
+
+        assert_eq!(playlist.first_playlist_link(), Some("720p60/index.m3u8"));
+    }
+
+    #[test]
+    fn test_relativize_uris() {
+        let mut media_list = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "https://cdn.example.com/live/720p/segment0.ts\n",
+            "#EXTINF:6.000,\n",
+            "https://CDN.example.com/live/720p/parts/segment1.ts?token=abc\n",
+            "#EXTINF:6.000,\n",
+            "https://cdn.example.com/live/1080p/segment2.ts\n",
+            "#EXTINF:6.000,\n",
+            "https://cdn.example.com/live-backup/720p/segment3.ts\n",
+            "#EXTINF:6.000,\n",
+            "https://other.example.com/live/720p/segment4.ts\n",
+            "#EXTINF:6.000,\n",
+            "segment5.ts\n",
+        ))
+        .unwrap();
+
+        media_list.relativize_uris("https://cdn.example.com/live/720p/index.m3u8?token=abc");
+
+        let uris = media_list
+            .media_segments
+            .iter()
+            .map(|s| s.uri.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            uris,
+            vec![
+                "segment0.ts",
+                "parts/segment1.ts?token=abc",
+                "../1080p/segment2.ts",
+                "../../live-backup/720p/segment3.ts",
+                "https://other.example.com/live/720p/segment4.ts",
+                "segment5.ts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ext_identifier() {
+        assert_eq!(
+            ext_identifier("#EXTM3U\n", ParseMode::Lenient),
+            Ok(("", "#EXTM3U\n"))
+        );
+        assert_eq!(
+            ext_identifier(
+                "#EXTM3U url-tvg=\"http://epg\"\r\n#EXTINF",
+                ParseMode::Lenient
+            ),
+            Ok(("#EXTINF", "#EXTM3U url-tvg=\"http://epg\"\r\n"))
+        );
+        assert!(ext_identifier("#EXTM3U url-tvg=\"http://epg\"\n", ParseMode::Strict).is_err());
+        assert_eq!(
+            ext_identifier("EXTM3U", ParseMode::Lenient),
+            Err(nom::Err::Error(M3U8ParserError::NomError(
+                "EXTM3U",
+                nom::error::ErrorKind::Tag,
+            )))
+        );
+    }
+
+    #[test]
+    fn test_header_attributes() {
+        let data = "#EXTM3U url-tvg=\"http://epg\" tvg-shift=2\n#EXTINF:-1,Channel\nhttp://example.com/channel.ts\n";
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.header_attributes.get("url-tvg"),
+            Some(&"\"http://epg\"".into())
+        );
+        assert_eq!(
+            media_list.header_attributes.get("tvg-shift"),
+            Some(&"2".into())
+        );
+        assert_eq!(
+            media_list.media_segments[0].title.as_deref(),
+            Some("Channel")
+        );
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert!(std::str::from_utf8(&output)
+            .unwrap()
+            .starts_with("#EXTM3U url-tvg=\"http://epg\" tvg-shift=2\n"));
+
+        assert!(read_media_list_strict(data).is_err());
+        assert!(read_media_list("#EXTM3U\n")
+            .unwrap()
+            .header_attributes
+            .is_empty());
+    }
+
+    #[test]
+    fn test_strict_missing_target_duration() {
+        let data = concat!("#EXTM3U\n", "#EXTINF:6.000,\n", "segment0.ts\n");
+
+        assert_eq!(read_media_list(data).unwrap().target_duration, 0);
+        assert_eq!(
+            read_media_list_strict(data),
+            Err(M3U8ParserError::AtLine {
+                line: 1,
+                source: Box::new(M3U8ParserError::MissingRequiredTag("TARGETDURATION")),
+            })
+        );
+
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+        );
 
-    let mut remaining_lines = i.lines();
+        assert_eq!(read_media_list_strict(data).unwrap().target_duration, 6);
+    }
 
-    let mut media_segments = Vec::new();
-    let mut ext_infos = Vec::new();
+    #[test]
+    fn test_strict_missing_stream_inf_attributes() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:RESOLUTION=1280x720\n",
+            "720p/index.m3u8\n",
+        );
 
-    let mut version = 0;
-    let mut target_duration = 0;
-    let mut media_sequence = 0;
+        assert_eq!(read_playlist(data).unwrap().ext_infos.len(), 1);
+        assert_eq!(
+            read_playlist_strict(data),
+            Err(M3U8ParserError::AtLine {
+                line: 2,
+                source: Box::new(M3U8ParserError::MissingRequiredAttribute {
+                    tag: "STREAM-INF",
+                    attr: "BANDWIDTH",
+                }),
+            })
+        );
 
-    let mut current_program_date_time = None;
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+            "#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=186842\n",
+        );
 
-    while let Some(line) = remaining_lines.next() {
-        let (i, ext_type) = ext_type::<MediaExtType>(line).finish()?;
+        assert_eq!(
+            read_playlist_strict(data),
+            Err(M3U8ParserError::AtLine {
+                line: 4,
+                source: Box::new(M3U8ParserError::MissingRequiredAttribute {
+                    tag: "I-FRAME-STREAM-INF",
+                    attr: "URI",
+                }),
+            })
+        );
 
-        match ext_type {
-            MediaExtType::DateRange => {
-                let (_, attributes) = attributes(i).finish()?;
+        let test_file = fs::read_to_string("./test_m3u8_files/iframe_playlist.m3u8").unwrap();
 
-                ext_infos.push(MediaExtInfo {
-                    ext_type,
-                    attributes,
-                })
-            }
-            MediaExtType::Unknown(_) => {
-                let (_, unknown_str) = to_end(i).finish()?;
+        assert!(read_playlist_strict(&test_file).is_ok());
+    }
 
-                let mut attributes = IndexMap::new();
+    #[test]
+    fn test_inf_titles() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:7\n",
+            "#EXTINF:6.006,Artist, Song\n",
+            "segment0.ts\n",
+            "#EXTINF:6.006,TYPE=ad,tvg-id=\"one\"\n",
+            "segment1.ts\n",
+            "#EXTINF:6.006\n",
+            "segment2.ts\n",
+        );
 
-                attributes.insert("UNKNOWN", unknown_str);
+        let media_list = read_media_list(data).unwrap();
 
-                ext_infos.push(MediaExtInfo {
-                    ext_type,
-                    attributes,
-                })
-            }
-            MediaExtType::ProgramDateTime => {
-                let (_, program_date_time) = not_newline(i).finish()?;
+        assert_eq!(
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| (s.duration, s.title.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (6.006, Some("Artist, Song")),
+                (6.006, Some("TYPE=ad,tvg-id=\"one\"")),
+                (6.006, None),
+            ]
+        );
+        assert!(matches!(
+            read_media_list_strict(data),
+            Err(M3U8ParserError::AtLine { line: 7, .. })
+        ));
+    }
 
-                current_program_date_time = Some(program_date_time.to_owned());
-            }
-            MediaExtType::Inf => {
-                let (_, (duration, tit)) = comma_sep_pair(i).finish()?;
+    #[test]
+    fn test_strict_dangling_inf() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXTINF:6.000,\n",
+        );
 
-                if let Some(stream_inf_location) = remaining_lines.next() {
-                    let duration = duration.parse::<f64>()?;
-                    let mut title = None;
+        assert_eq!(read_media_list(data).unwrap().media_segments.len(), 1);
+        assert_eq!(
+            read_media_list_strict(data),
+            Err(M3U8ParserError::AtLine {
+                line: 5,
+                source: Box::new(M3U8ParserError::MissingSegmentUri),
+            })
+        );
 
-                    if !tit.is_empty() {
-                        title = Some(tit.to_owned());
-                    }
+        let data = format!("{}#EXT-X-ENDLIST\n", data);
 
-                    let uri = stream_inf_location.to_owned();
+        assert_eq!(
+            read_media_list_strict(&data),
+            Err(M3U8ParserError::AtLine {
+                line: 5,
+                source: Box::new(M3U8ParserError::MissingSegmentUri),
+            })
+        );
 
-                    media_segments.push(MediaSegment {
-                        duration,
-                        title,
-                        uri,
-                        program_date_time: current_program_date_time.take(),
-                    })
-                }
-            }
-            MediaExtType::Version => {
-                let (_, ver) = not_newline(i).finish()?;
-                version = ver.parse::<u8>()?;
-            }
-            MediaExtType::TargetDuration => {
-                let (_, dur) = not_newline(i).finish()?;
-                target_duration = dur.parse::<u8>()?;
-            }
-            MediaExtType::MediaSequence => {
-                let (_, media_seq) = not_newline(i).finish()?;
-                media_sequence = media_seq.parse::<u32>()?;
-            }
-            MediaExtType::Discontinuity => {
-                let attributes = IndexMap::new();
+        let bad_duration = "#EXTM3U\n#EXTINF:six,\nsegment0.ts\n";
 
-                ext_infos.push(MediaExtInfo {
-                    ext_type,
-                    attributes,
-                })
-            }
-        }
+        assert!(matches!(
+            read_media_list_strict(bad_duration),
+            Err(M3U8ParserError::AtLine { line: 2, .. })
+        ));
+        assert!(read_media_list_strict("#EXT-X-TARGETDURATION:6\n").is_err());
     }
 
-    Ok(MediaList {
-        version,
-        target_duration,
-        media_sequence,
-        media_segments,
-        ext_infos,
-    })
-}
-
-#[cfg(test)]
-mod tests {
-    use std::fs;
-    use std::fs::OpenOptions;
-
-    use super::*;
-
     #[test]
-    fn test_read_playlist() {
-        let test_file = fs::read_to_string("./test_m3u8_files/playlist.m3u8").unwrap();
+    fn test_read_media_list_lenient() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXT-X-BITRATE:fast\n",
+            "#EXTINF:6.000,\n",
+            "segment1.ts\n",
+        );
 
-        let playlist = read_playlist(&test_file).unwrap();
-        let first_ext_info = playlist.ext_infos.get(0).unwrap();
+        assert!(read_media_list(data).is_err());
+
+        let (media_list, errors) = read_media_list_lenient(data);
 
         assert_eq!(
-            first_ext_info.ext_type,
-            PlaylistExtType::Unknown("TWITCH-INFO".to_owned())
-        );
-        assert_eq!(
-            first_ext_info.attributes.get("MANIFEST-NODE-TYPE"),
-            Some(&"\"weaver_cluster\"")
-        );
-        assert_eq!(
-            first_ext_info.attributes.get("BROADCAST-ID"),
-            Some(&"\"40032678348\"")
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| s.uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["segment0.ts", "segment1.ts"]
         );
-        assert_eq!(
-            first_ext_info.attributes.get("USER-COUNTRY"),
-            Some(&"\"GB\"")
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            M3U8ParserError::AtLine { line: 5, ref source }
+                if matches!(**source, M3U8ParserError::ParseIntError(_))
+        ));
+
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "#EXT-X-DISCONTINUITY\n",
+            "#EXTINF:six,\n",
+            "segment1.ts\n",
+            "#EXTINF:6.000,\n",
+            "segment2.ts\n",
         );
 
-        let media_1080p = playlist.ext_infos.get(1).unwrap();
-        assert_eq!(media_1080p.ext_type, PlaylistExtType::Media);
-        assert_eq!(media_1080p.attributes.get("TYPE"), Some(&"VIDEO"));
+        let (media_list, errors) = read_media_list_lenient(data);
+
         assert_eq!(
-            media_1080p.attributes.get("NAME"),
-            Some(&"\"1080p60 (source)\"")
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| (s.uri.as_str(), s.discontinuity))
+                .collect::<Vec<_>>(),
+            vec![("segment0.ts", false), ("segment2.ts", false)]
         );
-        assert_eq!(media_1080p.attributes.get("GROUP-ID"), Some(&"\"chunked\""));
+        assert!(matches!(
+            errors[..],
+            [M3U8ParserError::AtLine { line: 6, ref source }]
+                if matches!(**source, M3U8ParserError::ParseFloatError(_))
+        ));
 
-        let stream_inf_1080p = playlist.ext_infos.get(2).unwrap();
+        let (media_list, errors) = read_media_list_lenient("not a playlist\n");
 
-        assert_eq!(stream_inf_1080p.ext_type, PlaylistExtType::StreamInf);
-        assert_eq!(
-            stream_inf_1080p.attributes.get("RESOLUTION"),
-            Some(&"1920x1080")
+        assert!(media_list.media_segments.is_empty());
+        assert!(matches!(
+            errors[..],
+            [M3U8ParserError::AtLine { line: 1, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "# Encoded by a test\n",
+            "#EXTINF:6.000,\n",
+            "segment0.ts\n",
+            "\n",
+            "#EXTINF:6.000,\n",
+            "# The URI follows\n",
+            "segment1.ts\n",
+            "  \n",
+            "#EXT-X-BITRATE:fast\n",
         );
+
+        let media_list =
+            read_media_list(&data[..data.len() - "#EXT-X-BITRATE:fast\n".len()]).unwrap();
+
         assert_eq!(
-            stream_inf_1080p.attributes.get("VIDEO"),
-            Some(&"\"chunked\"")
+            media_list
+                .media_segments
+                .iter()
+                .map(|s| s.uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["segment0.ts", "segment1.ts"]
         );
+        assert!(media_list.ext_infos.is_empty());
+        assert!(matches!(
+            read_media_list(data),
+            Err(M3U8ParserError::AtLine { line: 11, .. })
+        ));
+
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "# Variants\n",
+            "\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH=3000000\n",
+            "720p/index.m3u8\n",
+        ))
+        .unwrap();
+
+        assert_eq!(playlist.ext_infos.len(), 1);
+        assert_eq!(playlist.first_playlist_link(), Some("720p/index.m3u8"));
     }
 
     #[test]
-    fn test_read_media_list() {
-        let test_file = fs::read_to_string("./test_m3u8_files/media_list.m3u8").unwrap();
+    fn test_unquote() {
+        assert_eq!(unquote("\"chunked\""), "chunked");
+        assert_eq!(unquote("1920x1080"), "1920x1080");
+        assert_eq!(unquote("\""), "\"");
+        assert_eq!(unquote("\"unbalanced"), "\"unbalanced");
+    }
 
-        let media_list = read_media_list(&test_file).unwrap();
+    #[test]
+    fn test_single_quoted_attributes() {
+        let (_, attrs) =
+            attributes("NAME='720p,60',CODECS=\"avc1,mp4a\"", ParseMode::Lenient).unwrap();
 
-        assert_eq!(media_list.version, 3);
-        assert_eq!(media_list.target_duration, 6);
-        assert_eq!(media_list.media_sequence, 4508);
+        assert_eq!(attrs.get("NAME"), Some(&"'720p,60'".into()));
+        assert_eq!(unquote(attrs.get("NAME").unwrap()), "720p,60");
+        assert_eq!(attrs.get("CODECS"), Some(&"\"avc1,mp4a\"".into()));
 
-        let first_ext_info = media_list.ext_infos.get(0).unwrap();
+        let (_, attrs) = attributes("NAME='720p\"", ParseMode::Lenient).unwrap();
+
+        assert_eq!(attrs.get("NAME"), Some(&"'720p\"".into()));
+        assert_eq!(unquote(attrs.get("NAME").unwrap()), "'720p\"");
+
+        let data = "#EXTM3U\n#EXT-X-MEDIA:TYPE=VIDEO,NAME='720p'\n";
+
+        let playlist = read_playlist(data).unwrap();
 
         assert_eq!(
-            first_ext_info.ext_type,
-            MediaExtType::Unknown("TWITCH-ELAPSED-SECS".to_owned())
+            playlist.ext_infos[0].attributes.get("NAME"),
+            Some(&"'720p'".into())
         );
-        assert_eq!(first_ext_info.attributes.get("UNKNOWN"), Some(&"9016.000"));
+        assert!(read_playlist_strict(data).is_err());
+        assert!(read_playlist_strict("#EXTM3U\n#EXT-X-MEDIA:TYPE=VIDEO,NAME='720p\"\n").is_err());
+        assert!(read_playlist_strict("#EXTM3U\n#EXT-X-MEDIA:TYPE=VIDEO,NAME=\"720p\"\n").is_ok());
+    }
 
-        let third_ext_info = media_list.ext_infos.get(2).unwrap();
-        assert_eq!(third_ext_info.ext_type, MediaExtType::DateRange,);
-        assert_eq!(
-            third_ext_info.attributes.get("CLASS"),
-            Some(&"\"twitch-stream-source\"")
+    #[test]
+    fn test_escaped_quotes() {
+        let data = concat!(
+            "#EXTM3U\n",
+            "#EXT-X-SESSION-DATA:DATA-ID=\"com.example.quote\",VALUE=\"he said \\\"hi\\\"\",LANGUAGE=\"en\"\n",
         );
+
+        let playlist = read_playlist_strict(data).unwrap();
+        let session_data = &playlist.ext_infos[0];
+
         assert_eq!(
-            third_ext_info.attributes.get("START-DATE"),
-            Some(&"\"2020-11-18T14:12:40.956Z\"")
+            session_data.attributes.get("VALUE"),
+            Some(&"\"he said \\\"hi\\\"\"".into())
         );
         assert_eq!(
-            third_ext_info.attributes.get("ID"),
-            Some(&"\"source-1605708760\"")
+            session_data.attr("VALUE").as_deref(),
+            Some("he said \"hi\"")
         );
-        assert_eq!(third_ext_info.attributes.get("END-ON-NEXT"), Some(&"YES"));
+        assert_eq!(session_data.attr("LANGUAGE").as_deref(), Some("en"));
         assert_eq!(
-            third_ext_info.attributes.get("X-TV-TWITCH-STREAM-SOURCE"),
-            Some(&"\"live\"")
+            playlist.session_data()[0].value.as_deref(),
+            Some("he said \"hi\"")
         );
 
-        let segment_info = media_list.media_segments.get(0).unwrap();
+        let mut output = Vec::new();
+        playlist.save(&mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), data);
+        let media_list = read_media_list(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-TARGETDURATION:6\n",
+            "#EXT-X-DATERANGE:ID=\"note\",X-NOTE=\"a \\\"b\\\"\"\n",
+        ))
+        .unwrap();
 
-        assert_eq!(segment_info.duration, 2.000);
-        assert_eq!(segment_info.title, Some("live".to_owned()));
         assert_eq!(
-            segment_info.uri, "https://video-edge-c6d608.lhr04.abs.hls.ttvnw.net/v1/segment/CrsEzRu838bWUuW9RMJzmO0XYcHHcASBvuHgb5RLq5NVkkDzcl8Fuk-cSTNUnlimYBLSWEbLD-wh6NRTc5dT8O4n-gVAHGHWNDmeFB7fU6uLudKXTvZ6TRUGWGtUg6nEhhyeQpbrxOD7gaK06BzPgGi4tt4N0MCRLxPRFu3a_XnkjkDZ6_Z9F848pp1IVLbogJuKMLeKt-hf4O_zRlI1XEgKM4XzlEhspGfkhoYZ-_L0px96CRUp7rKsYMSLZ6i_KmdXzT7NDm9x76UBKiIUenIf-N6AAgb8BZEDXJ5Vgwi8YxXsFcIaL5W35XFmP4iS9dIwEbneVU-Rn5bIrYxAuCLxq5xRdB-nXUsKr1Vhki11FrOg4Tsk-LYdTsS2X3-Z8NbJytbs8nPzRo3MpQlyWQIw4WshxZqZwQgb6g-jk85eAG6wP4tSCgcbgo4CeADFQIn33ynjYU1VkF7VzQkGNT1sj1HOMtCrET4aDTU8AF9CN_keWBUX7uOjMEpWJDs44GhfjHHmQqQ1kqmmEzPBxYRchWUrhLYNcDQHmJyFDpahCBXl5gKgHK-z7hBaUW8X8STjf7RhtDt9lFgy2b3SL_AVYLXbyMNs6jFAXqOEDe0o5MC0FAXBVgXNSBHEcqkVp0kiu5kL5jGBm37xeYpXxukrcCJ3BUA6w8SQQ9vjMHVgPzRbIL72NGca42z7e0GYGnXGaxqVj08aJtsnI1_U3chdp5k22pSCMpGpuU-lsLRodOomIdrmgkumvN0xXRIQpQ7h-p7kP9_pganXO5M5jhoMer127dU6GU0FarSp.ts"
+            media_list.ext_infos[0].attr("X-NOTE").as_deref(),
+            Some("a \"b\"")
         );
+        assert_eq!(unescape("C:\\\\dir\\file"), "C:\\dir\\file");
+        assert!(read_playlist_strict(
+            "#EXTM3U\n#EXT-X-SESSION-DATA:DATA-ID=\"a\",URI=\"C:\\data.json\"\n"
+        )
+        .is_ok());
     }
 
     #[test]
-    fn test_save_media_list() {
-        let curr_stream =
-            fs::read_to_string("./test_m3u8_files/twitch_ad_media_list.m3u8").unwrap();
+    fn test_padded_attributes() {
+        let (_, padded) = attributes(
+            "TYPE = VIDEO, NAME = \" x \" ,BANDWIDTH= 1430857 ",
+            ParseMode::Lenient,
+        )
+        .unwrap();
+        let (_, unpadded) = attributes(
+            "TYPE=VIDEO,NAME=\" x \",BANDWIDTH=1430857",
+            ParseMode::Lenient,
+        )
+        .unwrap();
 
-        let mut media_list = read_media_list(&curr_stream).unwrap();
+        assert_eq!(padded, unpadded);
 
-        media_list
-            .media_segments
-            .retain(|m| !m.title.as_deref().unwrap_or("").starts_with("Amazon"));
+        let playlist = read_playlist(concat!(
+            "#EXTM3U\n",
+            "#EXT-X-STREAM-INF:BANDWIDTH = 1430857, RESOLUTION = 852x480\n",
+            "480p/index.m3u8\n",
+        ))
+        .unwrap();
 
-        media_list.ext_infos.retain(|e| {
-            e.attributes.get("CLASS").unwrap_or(&"") != &"\"twitch-ad-quartile\""
-                && e.attributes.get("CLASS").unwrap_or(&"") != &"\"twitch-stitched-ad\""
-                && !e
-                    .attributes
-                    .get("X-TV-TWITCH-STREAM-SOURCE")
-                    .unwrap_or(&"")
-                    .starts_with(&"\"Amazon")
-                && e.ext_type != MediaExtType::Discontinuity
-                && e.ext_type != MediaExtType::Unknown("START".to_owned())
-        });
+        assert_eq!(playlist.ext_infos[0].bandwidth(), Ok(Some(1_430_857)));
+        assert_eq!(playlist.first_playlist_link(), Some("480p/index.m3u8"));
+    }
 
-        let mut outfile = OpenOptions::new()
-            .truncate(true)
-            .write(true)
-            .open("./test_m3u8_files/save_twitch_ad_media_list.m3u8")
-            .unwrap();
+    #[test]
+    fn test_read_media_list_quoted_uri() {
+        let data = "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.000,\n\"https://example.com/segment0.ts\"\n#EXTINF:6.000,\nhttps://example.com/segment1.ts\n";
 
-        media_list.save(&mut outfile).unwrap();
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(
+            media_list.media_segments[0].uri,
+            "https://example.com/segment0.ts"
+        );
+        assert_eq!(
+            media_list.media_segments[1].uri,
+            "https://example.com/segment1.ts"
+        );
     }
 
     #[test]
-    fn test_ext_identifier() {
-        assert_eq!(ext_identifier("#EXTM3U\n"), Ok(("", "#EXTM3U\n")));
+    fn test_read_media_list_inf_whitespace() {
+        let segment = |inf: &str, mode: ParseMode| {
+            let data = format!(
+                "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:{}\nsegment0.ts\n",
+                inf
+            );
+
+            read_media_list_mode(&data, mode)
+                .map(|m| m.media_segments[0].clone())
+                .unwrap()
+        };
+
+        let spaced = segment("6 , t", ParseMode::Lenient);
+
+        assert_eq!(spaced.duration, 6.0);
+        assert_eq!(spaced.title.as_deref(), Some("t"));
         assert_eq!(
-            ext_identifier("EXTM3U"),
-            Err(nom::Err::Error(M3U8ParserError::NomError(
-                "EXTM3U",
-                nom::error::ErrorKind::Tag,
-            )))
+            segment("6 , t", ParseMode::Strict).title.as_deref(),
+            Some(" t")
         );
+
+        let unspaced = segment("6,t", ParseMode::Lenient);
+
+        assert_eq!(unspaced.duration, 6.0);
+        assert_eq!(unspaced.title.as_deref(), Some("t"));
+
+        let untitled = segment("6 ,", ParseMode::Lenient);
+
+        assert_eq!(untitled.duration, 6.0);
+        assert_eq!(untitled.title, None);
+    }
+
+    #[test]
+    fn test_read_media_list_without_trailing_newline() {
+        let data = "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6.000,\nsegment0.ts\n#EXTINF:4.000,\nsegment1.ts";
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert_eq!(media_list.media_segments.len(), 2);
+        assert_eq!(media_list.media_segments[1].uri, "segment1.ts");
+        assert_eq!(media_list.media_segments[1].duration, 4.0);
+
+        let crlf_data = data.replace('\n', "\r\n");
+        let media_list = read_media_list(&crlf_data).unwrap();
+
+        assert_eq!(media_list.media_segments[1].uri, "segment1.ts");
     }
 
     #[test]
     fn test_attribute_key_val() {
         assert_eq!(
-            attribute_key_val("TYPE=VIDEO"),
+            attribute_key_val("TYPE=VIDEO", ParseMode::Lenient),
             (Ok(("", ("TYPE", "VIDEO"))))
         );
         assert_eq!(
-            attribute_key_val(r#"GROUP-ID="720p60""#),
+            attribute_key_val(r#"GROUP-ID="720p60""#, ParseMode::Lenient),
             (Ok(("", ("GROUP-ID", "\"720p60\""))))
         );
         assert_eq!(
-            attribute_key_val("CODECS=\"avc1.4D401F,mp4a.40.2\""),
+            attribute_key_val("CODECS=\"avc1.4D401F,mp4a.40.2\"", ParseMode::Lenient),
             (Ok(("", ("CODECS", "\"avc1.4D401F,mp4a.40.2\""))))
         );
     }
@@ -586,27 +8056,50 @@ mod tests {
     fn test_attributes() {
         let mut attributes_map = IndexMap::new();
 
-        attributes_map.insert("TYPE", "VIDEO");
-        attributes_map.insert("GROUP-ID", "\"720p60\"");
-        attributes_map.insert("NAME", "\"720p60\"");
-        attributes_map.insert("AUTOSELECT", "YES");
-        attributes_map.insert("DEFAULT", "YES");
+        attributes_map.insert("TYPE".into(), "VIDEO".into());
+        attributes_map.insert("GROUP-ID".into(), "\"720p60\"".into());
+        attributes_map.insert("NAME".into(), "\"720p60\"".into());
+        attributes_map.insert("AUTOSELECT".into(), "YES".into());
+        attributes_map.insert("DEFAULT".into(), "YES".into());
 
         assert_eq!(
-            attributes(r#"TYPE=VIDEO,GROUP-ID="720p60",NAME="720p60",AUTOSELECT=YES,DEFAULT=YES"#),
+            attributes(
+                r#"TYPE=VIDEO,GROUP-ID="720p60",NAME="720p60",AUTOSELECT=YES,DEFAULT=YES"#,
+                ParseMode::Lenient
+            ),
             Ok(("", attributes_map))
         );
     }
 
+    #[test]
+    fn test_attributes_trailing_input() {
+        let mut attributes_map = IndexMap::new();
+
+        attributes_map.insert("TYPE".into(), "VIDEO".into());
+
+        assert_eq!(
+            attributes("TYPE=VIDEO,", ParseMode::Lenient),
+            Ok((",", attributes_map.clone()))
+        );
+        assert_eq!(
+            attributes("TYPE=VIDEO,YES", ParseMode::Lenient),
+            Ok((",YES", attributes_map))
+        );
+        assert_eq!(
+            attributes("", ParseMode::Lenient),
+            Ok(("", IndexMap::new()))
+        );
+    }
+
     #[test]
     fn test_rejoin_attributes() {
         let mut attributes_map = IndexMap::new();
 
-        attributes_map.insert("TYPE", "VIDEO");
-        attributes_map.insert("GROUP-ID", "\"720p60\"");
-        attributes_map.insert("NAME", "\"720p60\"");
-        attributes_map.insert("AUTOSELECT", "YES");
-        attributes_map.insert("DEFAULT", "YES");
+        attributes_map.insert("TYPE".into(), "VIDEO".into());
+        attributes_map.insert("GROUP-ID".into(), "\"720p60\"".into());
+        attributes_map.insert("NAME".into(), "\"720p60\"".into());
+        attributes_map.insert("AUTOSELECT".into(), "YES".into());
+        attributes_map.insert("DEFAULT".into(), "YES".into());
 
         assert_eq!(
             rejoin_attributes(&attributes_map),
@@ -615,7 +8108,7 @@ mod tests {
 
         let mut attributes_map_unknown = IndexMap::new();
 
-        attributes_map_unknown.insert("UNKNOWN", "33064.367");
+        attributes_map_unknown.insert("UNKNOWN".into(), "33064.367".into());
 
         assert_eq!(
             rejoin_attributes(&attributes_map_unknown),
@@ -641,8 +8134,495 @@ mod tests {
             ext_type::<PlaylistExtType>("#EXT-XBANDWIDTH=630000"),
             Ok((
                 "",
-                PlaylistExtType::Unknown("-XBANDWIDTH=630000".to_owned())
+                PlaylistExtType::UnknownExt("-XBANDWIDTH=630000".to_owned())
             ))
         );
     }
+
+    #[test]
+    fn test_ext_type_display_roundtrip() {
+        for tag in &[
+            "-X-VERSION",
+            "-X-DISCONTINUITY-SEQUENCE",
+            "INF",
+            "ALB",
+            "-X-TWITCH-ELAPSED-SECS",
+            "-X-INF",
+            "TWITCH-ELAPSED-SECS",
+            "-XBANDWIDTH=630000",
+            "-X-",
+        ] {
+            let ext_type = MediaExtType::from(tag);
+
+            assert_eq!(format!("{}{}", ext_type.tag_prefix(), ext_type), *tag);
+        }
+
+        for tag in &[
+            "-X-MEDIA",
+            "-X-STREAM-INF",
+            "-X-SESSION-DATA",
+            "-X-TWITCH-INFO",
+            "TWITCH-INFO",
+            "-XBANDWIDTH=630000",
+        ] {
+            let ext_type = PlaylistExtType::from(tag);
+
+            assert_eq!(format!("{}{}", ext_type.tag_prefix(), ext_type), *tag);
+        }
+
+        assert_ne!(
+            MediaExtType::from("-X-TWITCH-INFO"),
+            MediaExtType::from("TWITCH-INFO")
+        );
+    }
+
+    #[test]
+    fn test_playlist_ext_type_display() {
+        for (ext_type, rendered) in &[
+            (PlaylistExtType::Media, "MEDIA"),
+            (PlaylistExtType::StreamInf, "STREAM-INF"),
+            (PlaylistExtType::IFrameStreamInf, "I-FRAME-STREAM-INF"),
+            (PlaylistExtType::SessionData, "SESSION-DATA"),
+            (PlaylistExtType::SessionKey, "SESSION-KEY"),
+            (PlaylistExtType::IndependentSegments, "INDEPENDENT-SEGMENTS"),
+            (PlaylistExtType::Start, "START"),
+            (PlaylistExtType::Define, "DEFINE"),
+            (PlaylistExtType::ContentSteering, "CONTENT-STEERING"),
+            (
+                PlaylistExtType::Unknown("TWITCH-INFO".to_owned()),
+                "TWITCH-INFO",
+            ),
+            (
+                PlaylistExtType::UnknownExt("-XBANDWIDTH=630000".to_owned()),
+                "-XBANDWIDTH=630000",
+            ),
+        ] {
+            assert_eq!(ext_type.to_string(), *rendered);
+        }
+    }
+
+    #[test]
+    fn test_media_ext_type_prefixes() {
+        assert_eq!(
+            ext_type("#EXTINF:2.000,live"),
+            Ok(("2.000,live", MediaExtType::Inf))
+        );
+        assert_eq!(
+            ext_type("#EXT-X-VERSION:3"),
+            Ok(("3", MediaExtType::Version))
+        );
+        assert_eq!(
+            ext_type("#EXT-X-INF:2.000,live"),
+            Ok(("2.000,live", MediaExtType::Unknown("INF".to_owned())))
+        );
+        assert_eq!(
+            ext_type("#EXTVERSION:3"),
+            Ok(("3", MediaExtType::UnknownExt("VERSION".to_owned())))
+        );
+        assert_eq!(
+            ext_type("#EXT-X-X-FOO:1"),
+            Ok(("1", MediaExtType::Unknown("X-FOO".to_owned())))
+        );
+        assert_eq!(
+            ext_type("#EXT-X-"),
+            Ok(("", MediaExtType::Unknown("".to_owned())))
+        );
+        assert_eq!(
+            ext_type("#EXTFOO"),
+            Ok(("", MediaExtType::UnknownExt("FOO".to_owned())))
+        );
+        assert_eq!(
+            ext_type("#EXTFOO:bar"),
+            Ok(("bar", MediaExtType::UnknownExt("FOO".to_owned())))
+        );
+        // `#EXTM3U` is consumed by `ext_identifier`, so a repeated one is just an unknown tag.
+        assert_eq!(
+            ext_type("#EXTM3U"),
+            Ok(("", MediaExtType::UnknownExt("M3U".to_owned())))
+        );
+        assert!(ext_type::<MediaExtType>("#EXINF:2.000,").is_err());
+
+        let data = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-INDEPENDENT-SEGMENTS\n#EXTFOO\n#EXTBAR:\n#EXTINF:6.000,\nsegment0.ts\n";
+
+        let media_list = read_media_list(data).unwrap();
+
+        assert!(media_list.ext_infos[0].attributes.is_empty());
+        assert_eq!(
+            media_list.ext_infos[1].attributes.get("UNKNOWN"),
+            Some(&"".into())
+        );
+
+        let mut output = Vec::new();
+        media_list.save(&mut output).unwrap();
+
+        assert_eq!(std::str::from_utf8(&output).unwrap(), data);
+    }
+
+    mod roundtrip {
+        use proptest::prelude::*;
+
+        use super::*;
+
+        fn key() -> impl Strategy<Value = Key> {
+            (
+                prop_oneof![
+                    Just(KeyMethod::Aes128),
+                    Just(KeyMethod::SampleAes),
+                    Just(KeyMethod::SampleAesCtr),
+                ],
+                proptest::option::of("[a-z0-9/:.,_-]{1,20}"),
+                proptest::option::of("0x[0-9A-F]{32}"),
+                proptest::option::of("[a-z0-9.:-]{1,20}"),
+                proptest::option::of("[0-9/]{1,5}"),
+            )
+                .prop_map(|(method, uri, iv, keyformat, keyformatversions)| Key {
+                    method,
+                    uri,
+                    iv,
+                    keyformat,
+                    keyformatversions,
+                })
+        }
+
+        fn map() -> impl Strategy<Value = MapInfo> {
+            (
+                "[a-z0-9/:.,_-]{1,20}",
+                proptest::option::of((any::<u64>(), proptest::option::of(any::<u64>()))),
+            )
+                .prop_map(|(uri, byte_range)| MapInfo {
+                    uri,
+                    byte_range: byte_range.map(|(length, offset)| ByteRange { length, offset }),
+                })
+        }
+
+        fn part() -> impl Strategy<Value = Part> {
+            (
+                0u32..100_000,
+                "[a-z0-9/_-]{1,20}\\.mp4",
+                any::<bool>(),
+                proptest::option::of((any::<u64>(), proptest::option::of(any::<u64>()))),
+            )
+                .prop_map(|(millis, uri, independent, byte_range)| Part {
+                    duration: f64::from(millis) / 1000.0,
+                    uri,
+                    independent,
+                    byte_range: byte_range.map(|(length, offset)| ByteRange { length, offset }),
+                })
+        }
+
+        fn segment() -> impl Strategy<Value = MediaSegment> {
+            (
+                0u32..100_000,
+                // Lenient parsing trims the space after the duration's comma.
+                proptest::option::of("[A-Za-z0-9,.-][A-Za-z0-9 ,.-]{0,19}"),
+                "[a-z0-9/_-]{1,30}\\.ts",
+                proptest::option::of("[0-9T:.Z-]{1,24}"),
+                any::<bool>(),
+                proptest::option::of("[A-Za-z0-9 ,.:-]{1,20}"),
+                proptest::option::of("[A-Za-z0-9 ,.:-]{1,20}"),
+                proptest::option::of("[A-Za-z0-9 ,.:-]{1,20}"),
+                proptest::option::of(proptest::option::of(0u32..1_000_000)),
+                (any::<bool>(), any::<bool>()),
+                (
+                    proptest::option::of(key()),
+                    proptest::collection::vec(part(), 0..3),
+                ),
+                (
+                    proptest::option::of(map()),
+                    proptest::option::of(any::<u32>()),
+                    proptest::option::of((any::<u64>(), proptest::option::of(any::<u64>()))),
+                ),
+            )
+                .prop_map(
+                    |(
+                        millis,
+                        title,
+                        uri,
+                        program_date_time,
+                        discontinuity,
+                        album,
+                        artist,
+                        genre,
+                        cue_out,
+                        (cue_in, gap),
+                        (key, parts),
+                        (map, bitrate, byte_range),
+                    )| {
+                        MediaSegment {
+                            duration: f64::from(millis) / 1000.0,
+                            title,
+                            uri,
+                            program_date_time,
+                            discontinuity,
+                            album,
+                            artist,
+                            genre,
+                            cue_out: cue_out.map(|millis| CueOut {
+                                duration: millis.map(|m| f64::from(m) / 1000.0),
+                            }),
+                            cue_in,
+                            key,
+                            map,
+                            gap,
+                            bitrate,
+                            byte_range: byte_range
+                                .map(|(length, offset)| ByteRange { length, offset }),
+                            parts,
+                        }
+                    },
+                )
+        }
+
+        fn attribute_value() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "[A-Za-z0-9.]{1,10}",
+                "[A-Za-z0-9.,:= -]{1,20}".prop_map(|v| format!("\"{}\"", v)),
+            ]
+        }
+
+        fn ext_info() -> impl Strategy<Value = MediaExtInfo<'static>> {
+            prop_oneof![
+                proptest::collection::vec(("[A-Z][A-Z0-9-]{0,10}", attribute_value()), 0..5)
+                    .prop_map(|attributes| MediaExtInfo {
+                        ext_type: MediaExtType::DateRange,
+                        attributes: attributes
+                            .into_iter()
+                            .map(|(k, v)| (k.into(), v.into()))
+                            .collect(),
+                        position: 0,
+                    }),
+                (
+                    "TEST-[A-Z]{1,10}",
+                    proptest::option::of("[A-Za-z0-9.,=]{1,20}")
+                )
+                    .prop_map(|(name, value)| {
+                        let mut attributes = Attributes::new();
+
+                        if let Some(value) = value {
+                            attributes.insert("UNKNOWN".into(), value.into());
+                        }
+
+                        MediaExtInfo {
+                            ext_type: MediaExtType::Unknown(name),
+                            attributes,
+                            position: 0,
+                        }
+                    }),
+                (
+                    "TEST[A-Z]{1,10}",
+                    proptest::option::of("[A-Za-z0-9.,=]{1,20}")
+                )
+                    .prop_map(|(name, value)| {
+                        let mut attributes = Attributes::new();
+
+                        if let Some(value) = value {
+                            attributes.insert("UNKNOWN".into(), value.into());
+                        }
+
+                        MediaExtInfo {
+                            ext_type: MediaExtType::UnknownExt(name),
+                            attributes,
+                            position: 0,
+                        }
+                    }),
+            ]
+        }
+
+        fn preload_hint() -> impl Strategy<Value = PreloadHint> {
+            (
+                prop_oneof![Just("PART"), Just("MAP")],
+                "[a-z0-9/_-]{1,20}\\.mp4",
+                proptest::option::of(any::<u64>()),
+                proptest::option::of(any::<u64>()),
+            )
+                .prop_map(|(hint_type, uri, byterange_start, byterange_length)| {
+                    PreloadHint {
+                        hint_type: hint_type.to_owned(),
+                        uri,
+                        byterange_start,
+                        byterange_length,
+                    }
+                })
+        }
+
+        fn server_control() -> impl Strategy<Value = ServerControl> {
+            (
+                proptest::option::of(0.0..1000.0),
+                any::<bool>(),
+                proptest::option::of(0.0..1000.0),
+                proptest::option::of(0.0..1000.0),
+                any::<bool>(),
+            )
+                .prop_map(
+                    |(
+                        can_skip_until,
+                        can_skip_dateranges,
+                        hold_back,
+                        part_hold_back,
+                        can_block_reload,
+                    )| ServerControl {
+                        can_skip_until,
+                        can_skip_dateranges,
+                        hold_back,
+                        part_hold_back,
+                        can_block_reload,
+                    },
+                )
+        }
+
+        fn rendition_report() -> impl Strategy<Value = RenditionReport> {
+            (
+                "[a-z0-9/_-]{1,20}\\.m3u8",
+                proptest::option::of(any::<u64>()),
+                proptest::option::of(any::<u32>()),
+            )
+                .prop_map(|(uri, last_msn, last_part)| RenditionReport {
+                    uri,
+                    last_msn,
+                    last_part,
+                })
+        }
+
+        fn media_list() -> impl Strategy<Value = MediaList<'static>> {
+            (
+                any::<u8>(),
+                proptest::option::of(prop_oneof![
+                    Just(PlaylistType::Vod),
+                    Just(PlaylistType::Event)
+                ]),
+                any::<u8>(),
+                any::<u32>(),
+                any::<u32>(),
+                proptest::collection::vec(segment(), 0..20),
+                proptest::collection::vec(ext_info(), 0..10),
+                any::<bool>(),
+                (
+                    any::<bool>(),
+                    any::<bool>(),
+                    proptest::option::of((-1000.0..1000.0, any::<bool>()).prop_map(
+                        |(time_offset, precise)| Start {
+                            time_offset,
+                            precise,
+                        },
+                    )),
+                    proptest::option::of(server_control()),
+                    proptest::collection::vec(
+                        ("[A-Za-z0-9_-]{1,10}", "[A-Za-z0-9./:-]{0,20}"),
+                        0..3,
+                    ),
+                ),
+                (
+                    proptest::collection::vec(preload_hint(), 0..3),
+                    proptest::collection::vec(rendition_report(), 0..3),
+                ),
+                proptest::option::of(
+                    (any::<u64>(), proptest::option::of("[a-z0-9\t-]{1,20}")).prop_map(
+                        |(skipped_segments, recently_removed_dateranges)| Skip {
+                            skipped_segments,
+                            recently_removed_dateranges,
+                        },
+                    ),
+                ),
+                prop_oneof![Just(LineEnding::Lf), Just(LineEnding::CrLf)],
+            )
+                .prop_map(
+                    |(
+                        version,
+                        playlist_type,
+                        target_duration,
+                        media_sequence,
+                        discontinuity_sequence,
+                        mut media_segments,
+                        mut ext_infos,
+                        end_list,
+                        (independent_segments, iframes_only, start, server_control, defines),
+                        (preload_hints, rendition_reports),
+                        skip,
+                        line_ending,
+                    )| {
+                        // A map or bitrate can't be ended, so once one is set it stays in effect.
+                        for i in 1..media_segments.len() {
+                            if media_segments[i].map.is_none() {
+                                media_segments[i].map = media_segments[i - 1].map.clone();
+                            }
+
+                            if media_segments[i].bitrate.is_none() {
+                                media_segments[i].bitrate = media_segments[i - 1].bitrate;
+                            }
+                        }
+
+                        // Spread the tags between the segments, keeping them in order.
+                        let ext_info_count = ext_infos.len();
+
+                        for (i, ext_info) in ext_infos.iter_mut().enumerate() {
+                            ext_info.position = i * (media_segments.len() + 1) / ext_info_count;
+                        }
+
+                        MediaList {
+                            version,
+                            playlist_type,
+                            target_duration,
+                            media_sequence,
+                            discontinuity_sequence,
+                            media_segments,
+                            ext_infos,
+                            end_list,
+                            independent_segments,
+                            iframes_only,
+                            start,
+                            server_control,
+                            defines: defines.into_iter().collect(),
+                            preload_hints,
+                            rendition_reports,
+                            skip,
+                            line_ending,
+                            header_attributes: Attributes::new(),
+                        }
+                    },
+                )
+        }
+
+        proptest! {
+            #[test]
+            fn test_save_read_media_list_roundtrip(media_list in media_list()) {
+                let mut output = Vec::new();
+                media_list.save(&mut output).unwrap();
+
+                let output = String::from_utf8(output).unwrap();
+                let reparsed = read_media_list(&output).unwrap();
+
+                prop_assert_eq!(reparsed.version, media_list.version);
+                prop_assert_eq!(reparsed.playlist_type, media_list.playlist_type);
+                prop_assert_eq!(reparsed.target_duration, media_list.target_duration);
+                prop_assert_eq!(reparsed.media_sequence, media_list.media_sequence);
+                prop_assert_eq!(reparsed.discontinuity_sequence, media_list.discontinuity_sequence);
+                prop_assert_eq!(reparsed.end_list, media_list.end_list);
+                prop_assert_eq!(reparsed.independent_segments, media_list.independent_segments);
+                prop_assert_eq!(reparsed.iframes_only, media_list.iframes_only);
+                prop_assert_eq!(reparsed.start, media_list.start);
+                prop_assert_eq!(reparsed.server_control, media_list.server_control);
+                prop_assert_eq!(&reparsed.defines, &media_list.defines);
+                prop_assert_eq!(&reparsed.preload_hints, &media_list.preload_hints);
+                prop_assert_eq!(&reparsed.rendition_reports, &media_list.rendition_reports);
+                prop_assert_eq!(&reparsed.skip, &media_list.skip);
+                prop_assert_eq!(reparsed.line_ending, media_list.line_ending);
+                prop_assert_eq!(&reparsed.media_segments, &media_list.media_segments);
+
+                // Discontinuities round-trip through `MediaSegment::discontinuity`.
+                let reparsed_ext_infos = reparsed
+                    .ext_infos
+                    .iter()
+                    .filter(|e| e.ext_type != MediaExtType::Discontinuity)
+                    .collect::<Vec<_>>();
+
+                prop_assert_eq!(reparsed_ext_infos.len(), media_list.ext_infos.len());
+
+                for (reparsed, original) in reparsed_ext_infos.iter().zip(&media_list.ext_infos) {
+                    prop_assert_eq!(&reparsed.ext_type, &original.ext_type);
+                    prop_assert_eq!(&reparsed.attributes, &original.attributes);
+                    prop_assert_eq!(reparsed.position, original.position);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,54 @@
+fn split_origin(uri: &str) -> Option<(&str, &str)> {
+    let authority_start = uri.find("://")? + 3;
+    let path_start = uri[authority_start..]
+        .find('/')
+        .map_or(uri.len(), |i| authority_start + i);
+
+    Some(uri.split_at(path_start))
+}
+
+/// Returns the authority of an absolute URI, or `None` if `uri` is relative.
+pub(crate) fn authority(uri: &str) -> Option<&str> {
+    let (origin, _) = split_origin(uri)?;
+
+    origin.split("://").nth(1)
+}
+
+fn split_suffix(path: &str) -> (&str, &str) {
+    path.split_at(path.find(['?', '#']).unwrap_or(path.len()))
+}
+
+pub(crate) fn relativize(base: &str, uri: &str) -> Option<String> {
+    let (base_origin, base_path) = split_origin(base)?;
+    let (uri_origin, uri_path) = split_origin(uri)?;
+
+    if !base_origin.eq_ignore_ascii_case(uri_origin) {
+        return None;
+    }
+
+    let (base_path, _) = split_suffix(base_path);
+    let (uri_path, uri_suffix) = split_suffix(uri_path);
+
+    let mut base_dirs = base_path.split('/').skip(1).collect::<Vec<_>>();
+    base_dirs.pop();
+
+    let uri_components = uri_path.split('/').skip(1).collect::<Vec<_>>();
+    let uri_dirs = &uri_components[..uri_components.len().saturating_sub(1)];
+
+    let common = base_dirs
+        .iter()
+        .zip(uri_dirs)
+        .take_while(|(b, u)| b == u)
+        .count();
+
+    let mut relative = "../".repeat(base_dirs.len() - common);
+    relative.push_str(&uri_components[common..].join("/"));
+
+    if relative.is_empty() {
+        relative.push_str("./");
+    }
+
+    relative.push_str(uri_suffix);
+
+    Some(relative)
+}